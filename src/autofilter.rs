@@ -0,0 +1,615 @@
+//! AutoFilter criteria.
+//!
+//! Excel stores a sheet's AutoFilter as a range plus per-column criteria in
+//! the worksheet XML, and hides the rows that did not match when the filter
+//! was last applied. Those hidden-row flags go stale as soon as values change
+//! (or are recalculated here), so the criteria are read and evaluated again
+//! against the exported cell values.
+//!
+//! umya-spreadsheet keeps only the filter range, so the criteria are read
+//! from the XLSX parts directly. XLS AutoFilters are not supported.
+
+use crate::error::{Error, Result};
+use crate::evaluator::{column_letters, parse_a1_range};
+use crate::links::{attribute, read_part, relationship_targets, resolve_part_path};
+use crate::writer::{serial_to_datetime, CellData, RawValue};
+use chrono::{Datelike, NaiveDateTime, Timelike};
+use quick_xml::events::{BytesStart, Event};
+use quick_xml::Reader;
+use std::collections::HashMap;
+use std::fs::File;
+use std::path::Path;
+
+/// The AutoFilter of a sheet.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AutoFilter {
+    /// Range of the filter as ((first_col, header_row), (last_col, last_row)).
+    pub range: ((u32, u32), (u32, u32)),
+    /// Criteria keyed by absolute 1-based column.
+    pub columns: Vec<(u32, Criteria)>,
+}
+
+/// Criteria of one filtered column.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Criteria {
+    /// A checked list of displayed values and date groups.
+    Values {
+        values: Vec<String>,
+        blank: bool,
+        dates: Vec<DateGroup>,
+    },
+    /// One or two comparisons ("greater than 100", "begins with A*").
+    Custom {
+        and: bool,
+        conditions: Vec<(Operator, String)>,
+    },
+    /// The top or bottom N items or percent.
+    Top10 { top: bool, percent: bool, value: f64 },
+    /// Values above or below the column average.
+    Average { above: bool },
+    /// Criteria that cannot be re-evaluated (colors, icons, relative dates).
+    Unsupported(String),
+}
+
+/// A date in a filter's value list, matched down to its grouping level.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DateGroup {
+    /// Year, month, day, hour, minute, second; unused levels are None.
+    pub parts: [Option<u32>; 6],
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Operator {
+    Equal,
+    NotEqual,
+    LessThan,
+    LessThanOrEqual,
+    GreaterThan,
+    GreaterThanOrEqual,
+}
+
+impl Operator {
+    fn parse(name: &str) -> Option<Self> {
+        Some(match name {
+            "" | "equal" => Operator::Equal,
+            "notEqual" => Operator::NotEqual,
+            "lessThan" => Operator::LessThan,
+            "lessThanOrEqual" => Operator::LessThanOrEqual,
+            "greaterThan" => Operator::GreaterThan,
+            "greaterThanOrEqual" => Operator::GreaterThanOrEqual,
+            _ => return None,
+        })
+    }
+
+    fn test(&self, ordering: std::cmp::Ordering) -> bool {
+        use std::cmp::Ordering::*;
+        match self {
+            Operator::Equal => ordering == Equal,
+            Operator::NotEqual => ordering != Equal,
+            Operator::LessThan => ordering == Less,
+            Operator::LessThanOrEqual => ordering != Greater,
+            Operator::GreaterThan => ordering == Greater,
+            Operator::GreaterThanOrEqual => ordering != Less,
+        }
+    }
+}
+
+/// Read the AutoFilters of an XLSX file, keyed by sheet name.
+pub fn read_autofilters(path: &Path) -> Result<HashMap<String, AutoFilter>> {
+    let invalid = |details: String| Error::InvalidExcel {
+        path: path.to_path_buf(),
+        details,
+    };
+
+    let file = File::open(path)?;
+    let mut archive = zip::ZipArchive::new(file).map_err(|e| invalid(e.to_string()))?;
+
+    let Some(workbook_xml) = read_part(&mut archive, "xl/workbook.xml") else {
+        return Ok(HashMap::new());
+    };
+    let workbook_rels = read_part(&mut archive, "xl/_rels/workbook.xml.rels").unwrap_or_default();
+    let rel_targets = relationship_targets(&workbook_rels).map_err(invalid)?;
+
+    let mut filters = HashMap::new();
+    for (name, rel_id) in sheet_relationships(&workbook_xml).map_err(invalid)? {
+        let Some(target) = rel_targets.get(&rel_id) else {
+            continue;
+        };
+        let Some(sheet_xml) = read_part(&mut archive, &resolve_part_path("xl", target)) else {
+            continue;
+        };
+        if let Some(filter) = parse_autofilter(&sheet_xml).map_err(invalid)? {
+            filters.insert(name, filter);
+        }
+    }
+
+    Ok(filters)
+}
+
+impl AutoFilter {
+    /// Whether each row of a sheet's grid passes the filter. Rows outside
+    /// the filter range, including its header row, always pass.
+    pub fn matching_rows(&self, sheet_name: &str, rows: &[Vec<CellData>], date1904: bool) -> Vec<bool> {
+        let ((_, header_row), (_, last_row)) = self.range;
+        let data = header_row as usize..(last_row as usize).min(rows.len());
+        let mut keep = vec![true; rows.len()];
+
+        for (col, criteria) in &self.columns {
+            if let Criteria::Unsupported(kind) = criteria {
+                eprintln!(
+                    "warning: {}: autofilter on column {} uses {}, which is not supported; ignored",
+                    sheet_name,
+                    column_letters(*col),
+                    kind
+                );
+                continue;
+            }
+
+            let cell = |row: usize| rows[row].get(*col as usize - 1);
+            let numbers: Vec<f64> = data
+                .clone()
+                .filter_map(|row| match cell(row).and_then(|c| c.raw.as_ref()) {
+                    Some(RawValue::Number(n)) => Some(*n),
+                    _ => None,
+                })
+                .collect();
+            let threshold = criteria.threshold(&numbers);
+
+            for row in data.clone() {
+                if keep[row] && !criteria.matches(cell(row), threshold, date1904) {
+                    keep[row] = false;
+                }
+            }
+        }
+
+        keep
+    }
+}
+
+impl Criteria {
+    /// The cut-off value for criteria that depend on the whole column.
+    fn threshold(&self, numbers: &[f64]) -> Option<f64> {
+        match self {
+            Criteria::Top10 { top, percent, value } => {
+                if numbers.is_empty() {
+                    return None;
+                }
+                let mut sorted = numbers.to_vec();
+                sorted.sort_by(|a, b| a.total_cmp(b));
+                if *top {
+                    sorted.reverse();
+                }
+                let count = if *percent {
+                    (sorted.len() as f64 * value / 100.0).ceil()
+                } else {
+                    *value
+                };
+                let count = (count.max(1.0) as usize).min(sorted.len());
+                Some(sorted[count - 1])
+            }
+            Criteria::Average { .. } if !numbers.is_empty() => {
+                Some(numbers.iter().sum::<f64>() / numbers.len() as f64)
+            }
+            _ => None,
+        }
+    }
+
+    fn matches(&self, cell: Option<&CellData>, threshold: Option<f64>, date1904: bool) -> bool {
+        let text = cell.map(|c| c.text.trim()).unwrap_or_default();
+        let raw = cell.and_then(|c| c.raw.as_ref());
+        let number = match raw {
+            Some(RawValue::Number(n)) => Some(*n),
+            _ => None,
+        };
+
+        match self {
+            Criteria::Values { values, blank, dates } => {
+                if text.is_empty() {
+                    return *blank;
+                }
+                values.iter().any(|v| v.trim().to_lowercase() == text.to_lowercase())
+                    || number
+                        .and_then(|n| serial_to_datetime(n, date1904))
+                        .is_some_and(|dt| dates.iter().any(|d| d.contains(&dt)))
+            }
+            Criteria::Custom { and, conditions } => {
+                let mut results = conditions
+                    .iter()
+                    .map(|(op, val)| custom_matches(*op, val, text, number));
+                if *and {
+                    results.all(|r| r)
+                } else {
+                    results.any(|r| r)
+                }
+            }
+            Criteria::Top10 { top, .. } => match (number, threshold) {
+                (Some(n), Some(t)) => if *top { n >= t } else { n <= t },
+                _ => false,
+            },
+            Criteria::Average { above } => match (number, threshold) {
+                (Some(n), Some(t)) => if *above { n > t } else { n < t },
+                _ => false,
+            },
+            Criteria::Unsupported(_) => true,
+        }
+    }
+}
+
+impl DateGroup {
+    fn contains(&self, dt: &NaiveDateTime) -> bool {
+        let actual = [dt.year() as u32, dt.month(), dt.day(), dt.hour(), dt.minute(), dt.second()];
+        self.parts
+            .iter()
+            .zip(actual)
+            .all(|(expected, actual)| expected.is_none_or(|e| e == actual))
+    }
+}
+
+/// Evaluate one custom filter condition. Numbers compare numerically when
+/// the criterion is numeric; text compares case-insensitively, with `*` and
+/// `?` wildcards for (not) equal.
+fn custom_matches(op: Operator, criterion: &str, text: &str, number: Option<f64>) -> bool {
+    // Excel writes "(Blanks)"/"(Non blanks)" as equal/notEqual to a space
+    if criterion.trim().is_empty() {
+        return op.test(if text.is_empty() {
+            std::cmp::Ordering::Equal
+        } else {
+            std::cmp::Ordering::Greater
+        });
+    }
+
+    // A numeric criterion compares numbers only; text cells fail an
+    // ordering comparison but can still equal "100" as text
+    if let Ok(c) = criterion.trim().parse::<f64>() {
+        match number {
+            Some(n) => return op.test(n.total_cmp(&c)),
+            None if !matches!(op, Operator::Equal | Operator::NotEqual) => return false,
+            None => {}
+        }
+    }
+
+    let text = text.to_lowercase();
+    let criterion = criterion.to_lowercase();
+    match op {
+        Operator::Equal => wildcard_match(&criterion, &text),
+        Operator::NotEqual => !wildcard_match(&criterion, &text),
+        _ if number.is_some() => false,
+        _ => op.test(text.as_str().cmp(criterion.as_str())),
+    }
+}
+
+/// Match text against an Excel wildcard pattern: `*` is any run, `?` any
+/// character, and `~` escapes the next character.
+fn wildcard_match(pattern: &str, text: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let text: Vec<char> = text.chars().collect();
+
+    fn go(p: &[char], t: &[char]) -> bool {
+        match p.first() {
+            None => t.is_empty(),
+            Some('*') => (0..=t.len()).any(|i| go(&p[1..], &t[i..])),
+            Some('?') => !t.is_empty() && go(&p[1..], &t[1..]),
+            Some('~') if p.len() > 1 => t.first() == Some(&p[1]) && go(&p[2..], &t[1..]),
+            Some(c) => t.first() == Some(c) && go(&p[1..], &t[1..]),
+        }
+    }
+
+    go(&pattern, &text)
+}
+
+/// Sheet names and relationship ids from workbook.xml, in order.
+fn sheet_relationships(xml: &str) -> std::result::Result<Vec<(String, String)>, String> {
+    let mut reader = Reader::from_str(xml);
+    let mut sheets = Vec::new();
+
+    loop {
+        match reader.read_event() {
+            Ok(Event::Start(e)) | Ok(Event::Empty(e)) if e.local_name().as_ref() == b"sheet" => {
+                if let (Some(name), Some(id)) = (attribute(&e, b"name"), attribute(&e, b"id")) {
+                    sheets.push((name, id));
+                }
+            }
+            Ok(Event::Eof) => break,
+            Err(e) => return Err(format!("workbook.xml: {}", e)),
+            _ => {}
+        }
+    }
+
+    Ok(sheets)
+}
+
+/// Parse the sheet-level `<autoFilter>` element of a worksheet part, if it
+/// has one. Filters saved with custom sheet views are nested deeper and are
+/// not the sheet's active filter.
+fn parse_autofilter(xml: &str) -> std::result::Result<Option<AutoFilter>, String> {
+    let mut reader = Reader::from_str(xml);
+    let mut filter: Option<AutoFilter> = None;
+    let mut column: Option<u32> = None;
+    let mut criteria: Option<Criteria> = None;
+    let mut depth = 0usize;
+
+    loop {
+        let event = reader.read_event().map_err(|e| format!("worksheet: {}", e))?;
+        // Number of enclosing elements; 1 for children of <worksheet>
+        let (element, is_empty, ancestors) = match event {
+            Event::Start(ref e) => {
+                depth += 1;
+                (e, false, depth - 1)
+            }
+            Event::Empty(ref e) => (e, true, depth),
+            Event::End(ref e) => {
+                depth = depth.saturating_sub(1);
+                match e.local_name().as_ref() {
+                    b"filterColumn" => {
+                        if let (Some(filter), Some(col), Some(criteria)) =
+                            (filter.as_mut(), column.take(), criteria.take())
+                        {
+                            filter.columns.push((col, criteria));
+                        }
+                    }
+                    b"autoFilter" if filter.is_some() => break,
+                    _ => {}
+                }
+                continue;
+            }
+            Event::Eof => break,
+            _ => continue,
+        };
+
+        match element.local_name().as_ref() {
+            b"autoFilter" if ancestors == 1 => {
+                let Some(range) = attribute(element, b"ref").and_then(|r| parse_a1_range(&r).ok()) else {
+                    continue;
+                };
+                filter = Some(AutoFilter { range, columns: Vec::new() });
+                if is_empty {
+                    break;
+                }
+            }
+            b"filterColumn" if filter.is_some() => {
+                let first_col = filter.as_ref().map_or(1, |f| f.range.0 .0);
+                column = attribute(element, b"colId")
+                    .and_then(|id| id.parse::<u32>().ok())
+                    .map(|id| first_col + id);
+            }
+            b"filters" if column.is_some() => {
+                criteria = Some(Criteria::Values {
+                    values: Vec::new(),
+                    blank: flag(element, b"blank"),
+                    dates: Vec::new(),
+                });
+            }
+            b"filter" => {
+                if let (Some(Criteria::Values { values, .. }), Some(val)) =
+                    (criteria.as_mut(), attribute(element, b"val"))
+                {
+                    values.push(val);
+                }
+            }
+            b"dateGroupItem" => {
+                if let Some(Criteria::Values { dates, .. }) = criteria.as_mut() {
+                    dates.push(date_group(element));
+                }
+            }
+            b"customFilters" if column.is_some() => {
+                criteria = Some(Criteria::Custom {
+                    and: flag(element, b"and"),
+                    conditions: Vec::new(),
+                });
+            }
+            b"customFilter" => {
+                let op = attribute(element, b"operator").unwrap_or_default();
+                match (criteria.as_mut(), Operator::parse(&op)) {
+                    (Some(Criteria::Custom { conditions, .. }), Some(op)) => {
+                        conditions.push((op, attribute(element, b"val").unwrap_or_default()));
+                    }
+                    (Some(_), None) => {
+                        criteria = Some(Criteria::Unsupported(format!("operator {}", op)));
+                    }
+                    _ => {}
+                }
+            }
+            b"top10" if column.is_some() => {
+                criteria = Some(Criteria::Top10 {
+                    top: attribute(element, b"top").is_none_or(|v| v != "0" && v != "false"),
+                    percent: flag(element, b"percent"),
+                    value: attribute(element, b"val")
+                        .and_then(|v| v.parse().ok())
+                        .unwrap_or(10.0),
+                });
+            }
+            b"dynamicFilter" if column.is_some() => {
+                let kind = attribute(element, b"type").unwrap_or_default();
+                criteria = Some(match kind.as_str() {
+                    "aboveAverage" => Criteria::Average { above: true },
+                    "belowAverage" => Criteria::Average { above: false },
+                    _ => Criteria::Unsupported(format!("dynamic filter {}", kind)),
+                });
+            }
+            b"colorFilter" | b"iconFilter" if column.is_some() => {
+                let kind = String::from_utf8_lossy(element.local_name().as_ref()).into_owned();
+                criteria = Some(Criteria::Unsupported(kind));
+            }
+            _ => {}
+        }
+    }
+
+    Ok(filter)
+}
+
+/// A boolean attribute ("1" or "true").
+fn flag(element: &BytesStart, name: &[u8]) -> bool {
+    attribute(element, name).is_some_and(|v| v == "1" || v == "true")
+}
+
+/// Parse a `<dateGroupItem>`, keeping the parts down to its grouping level.
+fn date_group(element: &BytesStart) -> DateGroup {
+    const LEVELS: [&[u8]; 6] = [b"year", b"month", b"day", b"hour", b"minute", b"second"];
+    let grouping = attribute(element, b"dateTimeGrouping").unwrap_or_default();
+    let depth = LEVELS
+        .iter()
+        .position(|level| grouping.as_bytes() == *level)
+        .map_or(LEVELS.len(), |i| i + 1);
+
+    let mut parts = [None; 6];
+    for (i, level) in LEVELS.iter().enumerate().take(depth) {
+        parts[i] = attribute(element, level).and_then(|v| v.parse().ok());
+    }
+    DateGroup { parts }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::writer::grid;
+
+    fn matching(criteria: Criteria, rows: &[Vec<CellData>]) -> Vec<bool> {
+        let filter = AutoFilter {
+            range: ((1, 1), (2, 6)),
+            columns: vec![(2, criteria)],
+        };
+        filter.matching_rows("Sheet1", rows, false)
+    }
+
+    #[test]
+    fn test_matching_rows_values() {
+        let rows = grid(&[
+            &["Region", "Amount"],
+            &["East", "10"],
+            &["West", "20"],
+            &["", ""],
+            &["east", "40"],
+            &["West", "50"],
+            &["West", "60"],
+        ]);
+        let filter = AutoFilter {
+            range: ((1, 1), (2, 6)),
+            columns: vec![(1, Criteria::Values { values: vec!["East".to_string()], blank: true, dates: vec![] })],
+        };
+
+        // The header and the row below the range (row 7) always pass
+        assert_eq!(
+            filter.matching_rows("Sheet1", &rows, false),
+            vec![true, true, false, true, true, false, true]
+        );
+
+        let no_blanks = AutoFilter {
+            columns: vec![(1, Criteria::Values { values: vec!["East".to_string()], blank: false, dates: vec![] })],
+            ..filter
+        };
+        assert_eq!(
+            no_blanks.matching_rows("Sheet1", &rows, false),
+            vec![true, true, false, false, true, false, true]
+        );
+    }
+
+    #[test]
+    fn test_matching_rows_top10_and_average() {
+        let rows = grid(&[&["Name", "Score"], &["a", "10"], &["b", "50"], &["c", "n/a"], &["d", "20"], &["e", "40"]]);
+
+        let top2 = Criteria::Top10 { top: true, percent: false, value: 2.0 };
+        assert_eq!(matching(top2, &rows), vec![true, false, true, false, false, true]);
+
+        // 50% of the four numbers, rounded up
+        let bottom_half = Criteria::Top10 { top: false, percent: true, value: 50.0 };
+        assert_eq!(matching(bottom_half, &rows), vec![true, true, false, false, true, false]);
+
+        // The average of 10, 50, 20 and 40 is 30
+        assert_eq!(
+            matching(Criteria::Average { above: true }, &rows),
+            vec![true, false, true, false, false, true]
+        );
+        assert_eq!(
+            matching(Criteria::Average { above: false }, &rows),
+            vec![true, true, false, false, true, false]
+        );
+    }
+
+    #[test]
+    fn test_matching_rows_dates() {
+        // 45366 is 2024-03-15 and 45383 is 2024-04-01
+        let rows = grid(&[&["Name", "Date"], &["a", "45366"], &["b", "45383"], &["c", "45366.5"]]);
+        let march = DateGroup { parts: [Some(2024), Some(3), None, None, None, None] };
+        let dates = Criteria::Values { values: vec![], blank: false, dates: vec![march.clone()] };
+        assert_eq!(matching(dates, &rows), vec![true, true, false, true]);
+
+        let noon = DateGroup { parts: [Some(2024), Some(3), Some(15), Some(12), None, None] };
+        let at = |s| serial_to_datetime(s, false).unwrap();
+        assert!(march.contains(&at(45366.0)));
+        assert!(!march.contains(&at(45383.0)));
+        assert!(noon.contains(&at(45366.5)));
+        assert!(!noon.contains(&at(45366.0)));
+    }
+
+    #[test]
+    fn test_matching_rows_unsupported_keeps_all() {
+        let rows = grid(&[&["Name", "Score"], &["a", "10"]]);
+        assert_eq!(matching(Criteria::Unsupported("colorFilter".to_string()), &rows), vec![true, true]);
+    }
+
+    #[test]
+    fn test_parse_autofilter() {
+        let xml = r#"<worksheet><sheetData/>
+            <autoFilter ref="B1:D20">
+              <filterColumn colId="0"><filters blank="1"><filter val="East"/></filters></filterColumn>
+              <filterColumn colId="2"><customFilters and="1">
+                <customFilter operator="greaterThanOrEqual" val="100"/>
+                <customFilter operator="lessThan" val="500"/>
+              </customFilters></filterColumn>
+            </autoFilter></worksheet>"#;
+
+        let filter = parse_autofilter(xml).unwrap().unwrap();
+        assert_eq!(filter.range, ((2, 1), (4, 20)));
+        assert_eq!(
+            filter.columns[0],
+            (2, Criteria::Values { values: vec!["East".to_string()], blank: true, dates: vec![] })
+        );
+        assert_eq!(
+            filter.columns[1],
+            (
+                4,
+                Criteria::Custom {
+                    and: true,
+                    conditions: vec![
+                        (Operator::GreaterThanOrEqual, "100".to_string()),
+                        (Operator::LessThan, "500".to_string()),
+                    ],
+                }
+            )
+        );
+    }
+
+    #[test]
+    fn test_parse_autofilter_ignores_custom_views() {
+        let nested = r#"<worksheet><sheetData/><customSheetViews><customSheetView guid="{0}">
+            <autoFilter ref="A1:B5"><filterColumn colId="0"><filters><filter val="x"/></filters></filterColumn></autoFilter>
+            </customSheetView></customSheetViews></worksheet>"#;
+        assert_eq!(parse_autofilter(nested).unwrap(), None);
+
+        let sheet_level = r#"<worksheet><sheetData/><autoFilter ref="A1:C9"/>
+            <customSheetViews><customSheetView guid="{0}"><autoFilter ref="A1:B5"/></customSheetView></customSheetViews>
+            </worksheet>"#;
+        assert_eq!(parse_autofilter(sheet_level).unwrap().unwrap().range, ((1, 1), (3, 9)));
+    }
+
+    #[test]
+    fn test_custom_matches() {
+        assert!(custom_matches(Operator::Equal, "a*", "Apple", None));
+        assert!(!custom_matches(Operator::Equal, "a*", "Pear", None));
+        assert!(custom_matches(Operator::NotEqual, "*an*", "Apple", None));
+        assert!(custom_matches(Operator::GreaterThan, "100", "150", Some(150.0)));
+        assert!(custom_matches(Operator::NotEqual, " ", "x", None));
+        assert!(!custom_matches(Operator::NotEqual, " ", "", None));
+        assert!(!custom_matches(Operator::GreaterThan, "100", "abc", None));
+        assert!(!custom_matches(Operator::LessThanOrEqual, "100", "abc", None));
+        assert!(custom_matches(Operator::Equal, "100", "100", None));
+        assert!(custom_matches(Operator::GreaterThan, "b", "cherry", None));
+    }
+
+    #[test]
+    fn test_wildcard_match() {
+        assert!(wildcard_match("a?c", "abc"));
+        assert!(wildcard_match("*~*", "5*"));
+        assert!(!wildcard_match("*~*", "5"));
+    }
+}
@@ -0,0 +1,210 @@
+//! Column selection by header name.
+//!
+//! `--columns "Account,Amount=Total,Date"` keeps the named columns in the
+//! given order, renaming `Amount` to `Total`. Headers are read from the first
+//! exported row, which is the header row once the preamble has been dropped.
+
+use crate::error::{Error, Result};
+use crate::writer::{CellData, RawValue};
+use clap::ValueEnum;
+
+/// How requested column names are matched against headers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, ValueEnum)]
+pub enum HeaderMatch {
+    /// Exact match after trimming whitespace
+    #[default]
+    Exact,
+    /// Ignore case
+    IgnoreCase,
+    /// Ignore case and punctuation, then allow small typos
+    Fuzzy,
+}
+
+/// A requested column, optionally renamed in the output.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ColumnRequest {
+    pub header: String,
+    pub rename: Option<String>,
+}
+
+/// Columns to export, in output order.
+#[derive(Debug, Clone)]
+pub struct ColumnSelection {
+    pub columns: Vec<ColumnRequest>,
+    pub matching: HeaderMatch,
+}
+
+impl ColumnSelection {
+    /// Parse a comma-separated list of `Header` or `Header=New Name`.
+    pub fn parse(list: &str, matching: HeaderMatch) -> Result<Self> {
+        let columns: Vec<ColumnRequest> = list
+            .split(',')
+            .map(str::trim)
+            .filter(|c| !c.is_empty())
+            .map(|c| match c.split_once('=') {
+                Some((header, rename)) => ColumnRequest {
+                    header: header.trim().to_string(),
+                    rename: Some(rename.trim().to_string()),
+                },
+                None => ColumnRequest {
+                    header: c.to_string(),
+                    rename: None,
+                },
+            })
+            .collect();
+
+        if columns.is_empty() {
+            return Err(Error::InvalidArgument("--columns lists no columns".to_string()));
+        }
+        Ok(ColumnSelection { columns, matching })
+    }
+
+    /// Replace each row with the selected columns, renaming headers.
+    pub fn apply(&self, sheet_name: &str, rows: &mut [Vec<CellData>]) -> Result<()> {
+        let headers: Vec<String> = rows
+            .first()
+            .map(|r| r.iter().map(|c| c.text.trim().to_string()).collect())
+            .unwrap_or_default();
+
+        let indices = self
+            .columns
+            .iter()
+            .map(|request| {
+                find_header(&request.header, &headers, self.matching).ok_or_else(|| {
+                    Error::ColumnNotFound {
+                        name: request.header.clone(),
+                        sheet: sheet_name.to_string(),
+                        available: headers
+                            .iter()
+                            .filter(|h| !h.is_empty())
+                            .cloned()
+                            .collect::<Vec<_>>()
+                            .join(", "),
+                    }
+                })
+            })
+            .collect::<Result<Vec<usize>>>()?;
+
+        for (i, row) in rows.iter_mut().enumerate() {
+            *row = indices
+                .iter()
+                .zip(&self.columns)
+                .map(|(&idx, request)| match (&request.rename, i) {
+                    (Some(name), 0) => CellData {
+                        raw: Some(RawValue::Text(name.clone())),
+                        text: name.clone(),
+                        ..row.get(idx).cloned().unwrap_or_default()
+                    },
+                    _ => row.get(idx).cloned().unwrap_or_default(),
+                })
+                .collect();
+        }
+
+        Ok(())
+    }
+}
+
+/// Find the 0-based index of a header.
+pub(crate) fn find_header(name: &str, headers: &[String], matching: HeaderMatch) -> Option<usize> {
+    let name = name.trim();
+    if let Some(idx) = headers.iter().position(|h| h == name) {
+        return Some(idx);
+    }
+
+    match matching {
+        HeaderMatch::Exact => None,
+        HeaderMatch::IgnoreCase => headers
+            .iter()
+            .position(|h| h.to_lowercase() == name.to_lowercase()),
+        HeaderMatch::Fuzzy => {
+            let wanted = normalize(name);
+            if let Some(idx) = headers.iter().position(|h| normalize(h) == wanted) {
+                return Some(idx);
+            }
+
+            // The closest header within a quarter of the name's length, as
+            // long as no other header is as close
+            let max_distance = (wanted.chars().count() / 4).max(1);
+            let mut distances: Vec<(usize, usize)> = headers
+                .iter()
+                .enumerate()
+                .filter(|(_, h)| !h.is_empty())
+                .map(|(i, h)| (edit_distance(&normalize(h), &wanted), i))
+                .filter(|&(d, _)| d <= max_distance)
+                .collect();
+            distances.sort();
+            match distances.as_slice() {
+                [(_, idx)] => Some(*idx),
+                [(best, idx), (second, _), ..] if best < second => Some(*idx),
+                _ => None,
+            }
+        }
+    }
+}
+
+/// Lowercase alphanumerics only ("Invoice #" and "invoice" match).
+fn normalize(header: &str) -> String {
+    header
+        .chars()
+        .filter(|c| c.is_alphanumeric())
+        .flat_map(char::to_lowercase)
+        .collect()
+}
+
+/// Levenshtein distance between two strings, in characters.
+pub(crate) fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+
+    for (i, ca) in a.chars().enumerate() {
+        let mut current = vec![i + 1; b.len() + 1];
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(ca != *cb);
+            current[j + 1] = substitution.min(previous[j + 1] + 1).min(current[j] + 1);
+        }
+        previous = current;
+    }
+
+    previous[b.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        let selection = ColumnSelection::parse("Account, Amount=Total ,Date", HeaderMatch::Exact).unwrap();
+        assert_eq!(
+            selection.columns[1],
+            ColumnRequest {
+                header: "Amount".to_string(),
+                rename: Some("Total".to_string()),
+            }
+        );
+        assert_eq!(selection.columns.len(), 3);
+        assert!(ColumnSelection::parse(" , ", HeaderMatch::Exact).is_err());
+    }
+
+    #[test]
+    fn test_find_header() {
+        let headers: Vec<String> = ["Account No.", "Amount", "Posting Date", "Amount (EUR)"]
+            .iter()
+            .map(|h| h.to_string())
+            .collect();
+
+        assert_eq!(find_header("Amount", &headers, HeaderMatch::Exact), Some(1));
+        assert_eq!(find_header("amount", &headers, HeaderMatch::Exact), None);
+        assert_eq!(find_header("amount", &headers, HeaderMatch::IgnoreCase), Some(1));
+        assert_eq!(find_header("account no", &headers, HeaderMatch::Fuzzy), Some(0));
+        assert_eq!(find_header("Postng Date", &headers, HeaderMatch::Fuzzy), Some(2));
+        assert_eq!(find_header("Balance", &headers, HeaderMatch::Fuzzy), None);
+    }
+
+    #[test]
+    fn test_edit_distance() {
+        assert_eq!(edit_distance("kitten", "sitting"), 3);
+        assert_eq!(edit_distance("", "abc"), 3);
+        assert_eq!(edit_distance("same", "same"), 0);
+    }
+}
@@ -9,8 +9,26 @@ pub enum Error {
     #[error("invalid Excel file: {path} ({details})")]
     InvalidExcel { path: PathBuf, details: String },
 
-    #[error("sheet \"{name}\" not found (available: {available})")]
-    SheetNotFound { name: String, available: String },
+    #[error("sheet \"{name}\" not found{suggestion} (available: {available})")]
+    SheetNotFound {
+        name: String,
+        /// A "; did you mean ..." hint, or empty.
+        suggestion: String,
+        available: String,
+    },
+
+    #[error("sheet name \"{name}\" is ambiguous; it matches {candidates} (use the exact name)")]
+    AmbiguousSheetName { name: String, candidates: String },
+
+    #[error("column \"{name}\" not found in sheet {sheet} (available: {available})")]
+    ColumnNotFound {
+        name: String,
+        sheet: String,
+        available: String,
+    },
+
+    #[error("no sheet matches {pattern} (available: {available})")]
+    NoSheetMatches { pattern: String, available: String },
 
     #[error("sheet index {index} out of range (have {count} sheets)")]
     SheetIndexOutOfRange { index: usize, count: usize },
@@ -18,12 +36,21 @@ pub enum Error {
     #[error("multiple sheets require -o <directory>")]
     MultipleSheetNoOutput,
 
+    #[error("invalid argument: {0}")]
+    InvalidArgument(String),
+
+    #[error("invalid override: {0}")]
+    InvalidOverride(String),
+
     #[error("unsupported file format: {0}")]
     UnsupportedFormat(String),
 
     #[error("failed to write CSV: {0}")]
     CsvWrite(#[from] csv::Error),
 
+    #[error("failed to write workbook: {0}")]
+    WorkbookWrite(String),
+
     #[error("IO error: {0}")]
     Io(#[from] std::io::Error),
 }
@@ -34,10 +61,16 @@ impl Error {
             Error::FileNotFound(_) => 1,
             Error::InvalidExcel { .. } => 2,
             Error::SheetNotFound { .. } => 3,
+            Error::AmbiguousSheetName { .. } => 3,
+            Error::ColumnNotFound { .. } => 3,
+            Error::NoSheetMatches { .. } => 3,
             Error::SheetIndexOutOfRange { .. } => 3,
             Error::MultipleSheetNoOutput => 3,
+            Error::InvalidArgument(_) => 3,
+            Error::InvalidOverride(_) => 3,
             Error::UnsupportedFormat(_) => 3,
             Error::CsvWrite(_) => 4,
+            Error::WorkbookWrite(_) => 4,
             Error::Io(_) => 1,
         }
     }
@@ -4,14 +4,18 @@
 //! allowing cell values to be recomputed based on their formulas.
 
 use crate::error::{Error, Result};
+use crate::links::{self, CachedValue, ExternalLink};
+use crate::writer::RawValue;
 use chrono::NaiveDate;
+use formualizer_eval::engine::DateSystem;
 use formualizer_workbook::{
     backends::UmyaAdapter,
     traits::{LoadStrategy, SpreadsheetReader},
     Workbook, WorkbookConfig,
 };
-use std::collections::HashMap;
-use std::path::Path;
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use umya_spreadsheet::Spreadsheet;
 
 /// Configuration for formula evaluation.
 #[derive(Debug, Clone, Default)]
@@ -22,19 +26,79 @@ pub struct EvalConfig {
 
     /// Override for TODAY() function (for reproducible output).
     pub today: Option<NaiveDate>,
+
+    /// Range overrides to apply before evaluation, in order.
+    /// Key format: "SheetName!B3:B40" or "B3:B40"; value is an inline list
+    /// (rows separated by `;`, columns by `,`) or `@file.csv`.
+    pub range_overrides: Vec<(String, String)>,
+
+    /// Allow range overrides to replace formula cells with constants.
+    pub replace_formulas: bool,
+
+    /// The workbook uses the 1904 date system.
+    pub date1904: bool,
+
+    /// Linked workbook locations, keyed by file name (with or without
+    /// extension) as it appears in external references.
+    pub links: HashMap<String, PathBuf>,
 }
 
 /// Evaluated workbook containing recalculated cell values.
 pub struct EvaluatedWorkbook {
     workbook: Workbook,
     sheet_names: Vec<String>,
+    /// The workbook as stored on disk, used to inspect formulas and cached
+    /// spill ranges that the engine does not expose.
+    source: Spreadsheet,
+    /// Spilled dynamic array values, keyed by sheet name then (row, col).
+    spills: HashMap<String, HashMap<(u32, u32), SpillCell>>,
+    /// Ranges of the arrays laid out in `spills`, keyed by sheet name then
+    /// anchor (row, col).
+    spill_ranges: HashMap<String, HashMap<(u32, u32), CellRange>>,
+    /// Sheet extents (max_col, max_row) after laying out spilled arrays.
+    spill_extents: HashMap<String, (u32, u32)>,
+    /// Cells replaced by `--set`/`--set-range`, as (sheet, row, col).
+    overridden: HashSet<(String, u32, u32)>,
+    /// The workbook uses the 1904 date system.
+    date1904: bool,
+}
+
+/// A cell covered by a dynamic array formula.
+#[derive(Debug, Clone)]
+enum SpillCell {
+    /// A value laid out from the anchor's array result.
+    Value(formualizer_workbook::LiteralValue),
+    /// The anchor could not spill because its range was obstructed.
+    Blocked,
+    /// A cell of the spill range recorded in the file that the recalculated
+    /// array no longer covers; its stored value is out of date.
+    Stale,
 }
 
 impl EvaluatedWorkbook {
     /// Load and evaluate an Excel file.
     pub fn load(path: &Path, config: &EvalConfig) -> Result<Self> {
+        Self::load_linked(path, config, &[])
+    }
+
+    /// Load and evaluate an Excel file reached through a chain of external
+    /// links. Workbooks already in the chain are not loaded again, so
+    /// circular links fall back to cached values instead of recursing.
+    fn load_linked(path: &Path, config: &EvalConfig, chain: &[PathBuf]) -> Result<Self> {
+        if !path.exists() {
+            return Err(Error::FileNotFound(path.to_path_buf()));
+        }
+
+        // Read the file once; the engine and `source` parse the same bytes
+        let bytes = std::fs::read(path)?;
+        let source = umya_spreadsheet::reader::xlsx::read_reader(std::io::Cursor::new(&bytes), true)
+            .map_err(|e| Error::InvalidExcel {
+                path: path.to_path_buf(),
+                details: e.to_string(),
+            })?;
+
         // Open with UmyaAdapter
-        let adapter = UmyaAdapter::open_path(path).map_err(|e| Error::InvalidExcel {
+        let adapter = UmyaAdapter::open_bytes(bytes).map_err(|e| Error::InvalidExcel {
             path: path.to_path_buf(),
             details: format!("formualizer: {}", e),
         })?;
@@ -46,7 +110,11 @@ impl EvaluatedWorkbook {
         })?;
 
         // Create workbook from adapter
-        let wb_config = WorkbookConfig::ephemeral();
+        // DATE(), TODAY() and date arithmetic count from the workbook's epoch
+        let mut wb_config = WorkbookConfig::ephemeral();
+        if config.date1904 {
+            wb_config.eval.date_system = DateSystem::Excel1904;
+        }
         let mut workbook =
             Workbook::from_reader(adapter, LoadStrategy::EagerAll, wb_config).map_err(|e| {
                 Error::InvalidExcel {
@@ -55,9 +123,30 @@ impl EvaluatedWorkbook {
                 }
             })?;
 
+        // Resolve references to other workbooks before anything is evaluated
+        let external = links::read_external_links(path)?;
+        if !external.is_empty() {
+            let mut chain = chain.to_vec();
+            chain.push(path.to_path_buf());
+            attach_external_links(&mut workbook, &source, &external, path, config, &chain)?;
+        }
+
+        // Apply range overrides, then single cells so they can refine a range
+        let mut overridden = HashSet::new();
+        for (range_ref, data) in &config.range_overrides {
+            overridden.extend(apply_range_override(
+                &mut workbook,
+                &source,
+                range_ref,
+                data,
+                &sheet_names,
+                config.replace_formulas,
+            )?);
+        }
+
         // Apply cell overrides
         for (cell_ref, value) in &config.overrides {
-            apply_override(&mut workbook, cell_ref, value, &sheet_names)?;
+            overridden.insert(apply_override(&mut workbook, cell_ref, value, &sheet_names)?);
         }
 
         // TODO: Handle config.today override for TODAY()/NOW() functions
@@ -69,19 +158,331 @@ impl EvaluatedWorkbook {
             details: format!("evaluation failed: {}", e),
         })?;
 
-        Ok(Self {
+        let mut evaluated = Self {
             workbook,
             sheet_names,
-        })
+            source,
+            spills: HashMap::new(),
+            spill_ranges: HashMap::new(),
+            spill_extents: HashMap::new(),
+            overridden,
+            date1904: config.date1904,
+        };
+        evaluated.layout_spills();
+
+        Ok(evaluated)
+    }
+
+    /// Lay out array results into their neighbouring cells, as Excel does for
+    /// dynamic array formulas such as `=SORT(A2:A50)` or `=FILTER(...)`.
+    ///
+    /// An array whose spill range overlaps a formula, a non-empty value or
+    /// another spill is not laid out; its anchor reports `#SPILL!` instead.
+    /// Cells of a spill range recorded in the file that the new array no
+    /// longer covers read as empty.
+    fn layout_spills(&mut self) {
+        use formualizer_workbook::LiteralValue;
+
+        for sheet in self.sheet_names.clone() {
+            let Some((max_col, max_row)) = self.base_dimensions(&sheet) else {
+                continue;
+            };
+
+            let mut cells: HashMap<(u32, u32), SpillCell> = HashMap::new();
+            let mut ranges: HashMap<(u32, u32), CellRange> = HashMap::new();
+            let (mut ext_col, mut ext_row) = (max_col, max_row);
+
+            for row in 1..=max_row {
+                for col in 1..=max_col {
+                    let Some(LiteralValue::Array(arr)) = self.workbook.get_value(&sheet, row, col)
+                    else {
+                        continue;
+                    };
+
+                    let height = arr.len() as u32;
+                    let width = arr.first().map(|r| r.len()).unwrap_or(0) as u32;
+                    if height == 0 || width == 0 {
+                        continue;
+                    }
+
+                    let cached = self.cached_spill_range(&sheet, row, col);
+                    let blocked = (row..row + height)
+                        .flat_map(|r| (col..col + width).map(move |c| (r, c)))
+                        .filter(|&pos| pos != (row, col))
+                        .any(|pos| {
+                            cells.contains_key(&pos) || self.is_occupied(&sheet, pos, cached)
+                        });
+
+                    if blocked {
+                        cells.insert((row, col), SpillCell::Blocked);
+                        continue;
+                    }
+
+                    for (dr, values) in arr.iter().enumerate() {
+                        for (dc, value) in values.iter().enumerate() {
+                            let pos = (row + dr as u32, col + dc as u32);
+                            cells.insert(pos, SpillCell::Value(value.clone()));
+                        }
+                    }
+                    ranges.insert((row, col), ((col, row), (col + width - 1, row + height - 1)));
+                    ext_row = ext_row.max(row + height - 1);
+                    ext_col = ext_col.max(col + width - 1);
+                }
+            }
+
+            // Cells a shrunken array left behind hold Excel's copy of the old
+            // spill, so they read as empty
+            for (anchor, ((c1, r1), (c2, r2))) in self.cached_spill_ranges(&sheet) {
+                for pos in (r1..=r2).flat_map(|r| (c1..=c2).map(move |c| (r, c))) {
+                    let overridden = self.overridden.contains(&(sheet.clone(), pos.0, pos.1));
+                    if pos != anchor && !overridden && !self.is_formula(&sheet, pos) {
+                        cells.entry(pos).or_insert(SpillCell::Stale);
+                    }
+                }
+            }
+
+            if !cells.is_empty() {
+                self.spills.insert(sheet.clone(), cells);
+                self.spill_ranges.insert(sheet.clone(), ranges);
+                self.spill_extents.insert(sheet, (ext_col, ext_row));
+            }
+        }
+    }
+
+    /// The spill range Excel recorded for an array formula when the file was
+    /// last saved, as ((first_col, first_row), (last_col, last_row)).
+    fn cached_spill_range(&self, sheet: &str, row: u32, col: u32) -> Option<CellRange> {
+        let cell = self.source.get_sheet_by_name(sheet)?.get_cell((col, row))?;
+        let reference = cell.get_formula_obj()?.get_reference();
+        if reference.is_empty() {
+            return None;
+        }
+        parse_a1_range(reference).ok()
+    }
+
+    /// The spill ranges Excel recorded for the array formulas of a sheet,
+    /// keyed by anchor (row, col).
+    fn cached_spill_ranges(&self, sheet: &str) -> Vec<((u32, u32), CellRange)> {
+        let Some(ws) = self.source.get_sheet_by_name(sheet) else {
+            return Vec::new();
+        };
+        ws.get_cell_collection()
+            .into_iter()
+            .filter(|cell| cell.is_formula())
+            .filter_map(|cell| {
+                let coordinate = cell.get_coordinate();
+                let (row, col) = (*coordinate.get_row_num(), *coordinate.get_col_num());
+                Some(((row, col), self.cached_spill_range(sheet, row, col)?))
+            })
+            .collect()
+    }
+
+    /// Whether a cell of the workbook as stored on disk holds a formula.
+    fn is_formula(&self, sheet: &str, (row, col): (u32, u32)) -> bool {
+        self.source
+            .get_sheet_by_name(sheet)
+            .and_then(|s| s.get_cell((col, row)))
+            .is_some_and(|c| c.is_formula())
+    }
+
+    /// Whether a cell holds content that would obstruct a spill.
+    ///
+    /// Values inside the anchor's cached spill range are Excel's stored copy of
+    /// the previous spill and do not count as obstructions.
+    fn is_occupied(&self, sheet: &str, (row, col): (u32, u32), cached: Option<CellRange>) -> bool {
+        use formualizer_workbook::LiteralValue;
+
+        if self.is_formula(sheet, (row, col)) {
+            return true;
+        }
+
+        let in_cached = cached.is_some_and(|((c1, r1), (c2, r2))| {
+            (c1..=c2).contains(&col) && (r1..=r2).contains(&row)
+        });
+        if in_cached {
+            return false;
+        }
+
+        !matches!(
+            self.workbook.get_value(sheet, row, col),
+            None | Some(LiteralValue::Empty)
+        )
+    }
+
+    /// Write the workbook, with overrides applied and recalculated values
+    /// cached, to an XLSX file.
+    ///
+    /// Formulas, styles and number formats are kept from the original file;
+    /// only the cached results of formula cells, spilled array values and
+    /// overridden cells are updated, so Excel shows the new results on open.
+    /// Array formulas record their new spill range, and cells of a previous
+    /// spill that the new array no longer covers are cleared.
+    pub fn save(&self, path: &Path) -> Result<()> {
+        use formualizer_workbook::LiteralValue;
+
+        let mut book = self.source.clone();
+
+        for sheet_name in &self.sheet_names {
+            let (max_col, max_row) = self.get_sheet_dimensions(sheet_name).unwrap_or((0, 0));
+            let Some(sheet) = book.get_sheet_by_name_mut(sheet_name) else {
+                continue;
+            };
+
+            for row in 1..=max_row {
+                for col in 1..=max_col {
+                    let overridden = self.overridden.contains(&(sheet_name.clone(), row, col));
+                    let spilled = self
+                        .spills
+                        .get(sheet_name)
+                        .is_some_and(|s| s.contains_key(&(row, col)));
+                    let formula = sheet
+                        .get_cell((col, row))
+                        .filter(|c| c.is_formula())
+                        .and_then(|c| c.get_formula_obj().cloned());
+
+                    if !overridden && !spilled && formula.is_none() {
+                        continue;
+                    }
+
+                    let value = self.get_literal(sheet_name, row, col).unwrap_or(LiteralValue::Empty);
+                    if matches!(value, LiteralValue::Empty) && sheet.get_cell((col, row)).is_none() {
+                        continue;
+                    }
+
+                    let cell = sheet.get_cell_mut((col, row));
+                    match value {
+                        LiteralValue::Empty => {
+                            cell.set_value_string("");
+                        }
+                        LiteralValue::Int(i) => {
+                            cell.set_value_number(i as f64);
+                        }
+                        LiteralValue::Number(n) => {
+                            cell.set_value_number(n);
+                        }
+                        LiteralValue::Boolean(b) => {
+                            cell.set_value_bool(b);
+                        }
+                        LiteralValue::Error(e) => {
+                            cell.set_error(&e.to_string());
+                        }
+                        other => match literal_to_serial(&other, self.date1904) {
+                            Some(serial) => {
+                                cell.set_value_number(serial);
+                            }
+                            None => {
+                                cell.set_value_string(self.format_value(other));
+                            }
+                        },
+                    }
+
+                    // Keep the formula unless an override replaced the cell
+                    if let (Some(mut formula), false) = (formula, overridden) {
+                        if !formula.get_reference().is_empty() {
+                            formula.set_reference(self.spill_reference(sheet_name, row, col));
+                        }
+                        cell.get_cell_value_mut().set_formula_obj(formula);
+                    }
+                }
+            }
+        }
+
+        umya_spreadsheet::writer::xlsx::write(&book, path)
+            .map_err(|e| Error::WorkbookWrite(format!("{:?}: {}", path, e)))
+    }
+
+    /// The range to record for an array formula: its laid-out spill range, or
+    /// the anchor cell alone when the result did not spill.
+    fn spill_reference(&self, sheet: &str, row: u32, col: u32) -> String {
+        let anchor = format!("{}{}", column_letters(col), row);
+        match self.spill_ranges.get(sheet).and_then(|r| r.get(&(row, col))) {
+            Some(&(_, (last_col, last_row))) if (last_col, last_row) != (col, row) => {
+                format!("{}:{}{}", anchor, column_letters(last_col), last_row)
+            }
+            _ => anchor,
+        }
+    }
+
+    /// Get the evaluated value of a cell, with spilled arrays laid out.
+    fn get_literal(&self, sheet: &str, row: u32, col: u32) -> Option<formualizer_workbook::LiteralValue> {
+        if let Some(cell) = self.spills.get(sheet).and_then(|s| s.get(&(row, col))) {
+            return Some(match cell {
+                SpillCell::Value(v) => v.clone(),
+                SpillCell::Blocked => error_literal("#SPILL!"),
+                SpillCell::Stale => formualizer_workbook::LiteralValue::Empty,
+            });
+        }
+
+        self.workbook.get_value(sheet, row, col)
+    }
+
+    /// Get a sheet of the workbook as stored on disk, for data the engine does
+    /// not track (comments, hyperlinks, visibility).
+    pub fn source_sheet(&self, sheet: &str) -> Option<&umya_spreadsheet::Worksheet> {
+        self.source.get_sheet_by_name(sheet)
+    }
+
+    /// Get a cell of the workbook as stored on disk, before overrides and
+    /// recalculation.
+    pub fn source_cell(&self, sheet: &str, row: u32, col: u32) -> Option<&umya_spreadsheet::Cell> {
+        self.source.get_sheet_by_name(sheet)?.get_cell((col, row))
     }
 
-    /// Get the evaluated value of a cell as a formatted string.
-    pub fn get_value(&self, sheet: &str, row: u32, col: u32) -> Option<String> {
+    /// Whether a cell was replaced by `--set` or `--set-range`.
+    pub fn is_overridden(&self, sheet: &str, row: u32, col: u32) -> bool {
+        self.overridden.contains(&(sheet.to_string(), row, col))
+    }
+
+    /// Get the evaluated value of a cell for number formatting, or None for an
+    /// empty cell. Dates and times are returned as Excel serial numbers.
+    pub fn get_raw_value(&self, sheet: &str, row: u32, col: u32) -> Option<RawValue> {
+        use formualizer_workbook::LiteralValue;
+
+        let value = self.get_literal(sheet, row, col)?;
+        match value {
+            LiteralValue::Empty => None,
+            LiteralValue::Boolean(_)
+            | LiteralValue::Text(_)
+            | LiteralValue::Error(_)
+            | LiteralValue::Array(_)
+            | LiteralValue::Pending => Some(RawValue::Text(self.format_value(value))),
+            other => literal_to_serial(&other, self.date1904).map(RawValue::Number),
+        }
+    }
+
+    /// Get the number format of a cell in the original workbook as
+    /// (built-in format ID, format code).
+    ///
+    /// Formulas returning a date or time into a "General" cell get an ISO
+    /// format, as Excel would apply a date format to such a cell on entry.
+    pub fn get_number_format(&self, sheet: &str, row: u32, col: u32) -> (u32, String) {
         use formualizer_workbook::LiteralValue;
 
-        let value = self.workbook.get_value(sheet, row, col)?;
+        let (format_id, format_code) = self
+            .source
+            .get_sheet_by_name(sheet)
+            .and_then(|s| s.get_cell((col, row)))
+            .map(crate::writer::cell_number_format)
+            .unwrap_or_else(|| (0, "General".to_string()));
 
-        Some(match value {
+        if !format_code.eq_ignore_ascii_case("General") {
+            return (format_id, format_code);
+        }
+
+        let iso = match self.get_literal(sheet, row, col) {
+            Some(LiteralValue::Date(_)) => "yyyy-mm-dd",
+            Some(LiteralValue::DateTime(_)) => "yyyy-mm-dd hh:mm:ss",
+            Some(LiteralValue::Time(_)) | Some(LiteralValue::Duration(_)) => "[h]:mm:ss",
+            _ => return (format_id, format_code),
+        };
+        (164, iso.to_string())
+    }
+
+    /// Format an evaluated value as a string.
+    fn format_value(&self, value: formualizer_workbook::LiteralValue) -> String {
+        use formualizer_workbook::LiteralValue;
+
+        match value {
             LiteralValue::Empty => String::new(),
             LiteralValue::Int(i) => i.to_string(),
             LiteralValue::Number(n) => {
@@ -118,7 +519,7 @@ impl EvaluatedWorkbook {
                     .join(";")
             }
             LiteralValue::Pending => String::from("#PENDING"),
-        })
+        }
     }
 
     /// Get list of sheet names.
@@ -126,8 +527,18 @@ impl EvaluatedWorkbook {
         &self.sheet_names
     }
 
-    /// Get the dimensions (max_col, max_row) of a sheet.
+    /// Get the dimensions (max_col, max_row) of a sheet, including any
+    /// spilled array ranges that extend past the stored cells.
     pub fn get_sheet_dimensions(&self, sheet: &str) -> Option<(u32, u32)> {
+        let (cols, rows) = self.base_dimensions(sheet)?;
+        Some(match self.spill_extents.get(sheet) {
+            Some(&(ext_col, ext_row)) => (cols.max(ext_col), rows.max(ext_row)),
+            None => (cols, rows),
+        })
+    }
+
+    /// Get the dimensions (max_col, max_row) of a sheet as stored in the engine.
+    fn base_dimensions(&self, sheet: &str) -> Option<(u32, u32)> {
         // Get dimensions from the engine's sheet store
         let sheet_store = self.workbook.engine().sheet_store();
         let asheet = sheet_store.sheet(sheet)?;
@@ -139,6 +550,175 @@ impl EvaluatedWorkbook {
     }
 }
 
+/// Load the workbooks referenced by external links into the engine and point
+/// formulas at them.
+///
+/// Each linked sheet is copied into a hidden engine sheet (see
+/// [`links::linked_sheet_name`]). When a linked file cannot be found or
+/// loaded, the values Excel cached for it in the XLSX are used instead.
+fn attach_external_links(
+    workbook: &mut Workbook,
+    source: &Spreadsheet,
+    external: &[ExternalLink],
+    path: &Path,
+    config: &EvalConfig,
+    chain: &[PathBuf],
+) -> Result<()> {
+    use formualizer_workbook::LiteralValue;
+
+    let base_dir = path.parent().unwrap_or_else(|| Path::new("."));
+    let engine_err = |e: String| Error::InvalidExcel {
+        path: path.to_path_buf(),
+        details: format!("external link: {}", e),
+    };
+
+    for link in external {
+        let linked = match links::resolve_link_path(link, base_dir, &config.links) {
+            None => {
+                eprintln!(
+                    "warning: linked workbook {} not found, using cached values",
+                    link.target
+                );
+                None
+            }
+            Some(p) if chain.contains(&p) => {
+                eprintln!(
+                    "warning: linked workbook {:?} links back to a workbook being loaded, using cached values",
+                    p
+                );
+                None
+            }
+            Some(p) => {
+                let linked_config = EvalConfig {
+                    today: config.today,
+                    links: config.links.clone(),
+                    date1904: crate::reader::detect_date1904(&p).unwrap_or(false),
+                    ..Default::default()
+                };
+                match EvaluatedWorkbook::load_linked(&p, &linked_config, chain) {
+                    Ok(wb) => Some(wb),
+                    Err(e) => {
+                        eprintln!(
+                            "warning: failed to load linked workbook {:?}: {}; using cached values",
+                            p, e
+                        );
+                        None
+                    }
+                }
+            }
+        };
+
+        for sheet in &link.sheet_names {
+            let name = links::linked_sheet_name(link, sheet);
+            workbook.add_sheet(&name).map_err(|e| engine_err(e.to_string()))?;
+
+            match linked.as_ref().filter(|wb| wb.sheet_names().contains(sheet)) {
+                Some(wb) => {
+                    let (max_col, max_row) = wb.get_sheet_dimensions(sheet).unwrap_or((0, 0));
+                    for row in 1..=max_row {
+                        for col in 1..=max_col {
+                            match wb.get_literal(sheet, row, col) {
+                                None | Some(LiteralValue::Empty) => {}
+                                Some(value) => workbook
+                                    .set_value(&name, row, col, value)
+                                    .map_err(|e| engine_err(e.to_string()))?,
+                            }
+                        }
+                    }
+                }
+                None => {
+                    for ((cached_sheet, row, col), value) in &link.cached {
+                        if cached_sheet != sheet {
+                            continue;
+                        }
+                        let literal = match value {
+                            CachedValue::Number(n) => LiteralValue::Number(*n),
+                            CachedValue::Text(s) => LiteralValue::Text(s.clone()),
+                            CachedValue::Boolean(b) => LiteralValue::Boolean(*b),
+                            CachedValue::Error(e) => error_literal(e),
+                        };
+                        workbook
+                            .set_value(&name, *row, *col, literal)
+                            .map_err(|e| engine_err(e.to_string()))?;
+                    }
+                }
+            }
+        }
+    }
+
+    // Point formulas at the linked sheets
+    let resolve = |book: &str, sheet: &str| {
+        let link = match book.parse::<usize>() {
+            Ok(index) => external.iter().find(|l| l.index == index),
+            Err(_) => external.iter().find(|l| l.matches(book)),
+        }?;
+        link.sheet_names
+            .iter()
+            .any(|s| s == sheet)
+            .then(|| links::linked_sheet_name(link, sheet))
+    };
+
+    for sheet in source.get_sheet_collection() {
+        for cell in sheet.get_cell_collection() {
+            if !cell.is_formula() {
+                continue;
+            }
+            let formula = cell.get_formula();
+            let rewritten = links::rewrite_external_refs(formula, resolve);
+            if rewritten == formula {
+                continue;
+            }
+
+            let coordinate = cell.get_coordinate();
+            workbook
+                .set_formula(
+                    sheet.get_name(),
+                    *coordinate.get_row_num(),
+                    *coordinate.get_col_num(),
+                    &format!("={}", rewritten),
+                )
+                .map_err(|e| engine_err(e.to_string()))?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Convert a numeric, date or time value to an Excel serial number (days
+/// since 1899-12-30, or since 1904-01-01 in the 1904 date system, with the
+/// time of day as the fraction).
+fn literal_to_serial(value: &formualizer_workbook::LiteralValue, date1904: bool) -> Option<f64> {
+    use formualizer_workbook::LiteralValue;
+
+    let epoch = if date1904 {
+        NaiveDate::from_ymd_opt(1904, 1, 1)?
+    } else {
+        NaiveDate::from_ymd_opt(1899, 12, 30)?
+    }
+    .and_hms_opt(0, 0, 0)?;
+    let seconds_per_day = 86_400.0;
+
+    match value {
+        LiteralValue::Int(i) => Some(*i as f64),
+        LiteralValue::Number(n) => Some(*n),
+        LiteralValue::Date(d) => Some((*d - epoch.date()).num_days() as f64),
+        LiteralValue::DateTime(dt) => {
+            Some((*dt - epoch).num_milliseconds() as f64 / 1000.0 / seconds_per_day)
+        }
+        LiteralValue::Time(t) => {
+            let midnight = chrono::NaiveTime::from_hms_opt(0, 0, 0)?;
+            Some((*t - midnight).num_milliseconds() as f64 / 1000.0 / seconds_per_day)
+        }
+        LiteralValue::Duration(dur) => Some(dur.num_milliseconds() as f64 / 1000.0 / seconds_per_day),
+        _ => None,
+    }
+}
+
+/// Build an error value from its Excel text (e.g. "#REF!").
+fn error_literal(text: &str) -> formualizer_workbook::LiteralValue {
+    formualizer_workbook::LiteralValue::Error(formualizer_workbook::ExcelError::from_error_string(text))
+}
+
 /// Format a LiteralValue to string (helper for arrays).
 fn format_literal(value: &formualizer_workbook::LiteralValue) -> String {
     use formualizer_workbook::LiteralValue;
@@ -174,7 +754,7 @@ fn apply_override(
     cell_ref: &str,
     value: &str,
     sheet_names: &[String],
-) -> Result<()> {
+) -> Result<(String, u32, u32)> {
     let (sheet, col, row) = parse_cell_reference(cell_ref, sheet_names)?;
 
     // Parse value to appropriate type
@@ -187,34 +767,225 @@ fn apply_override(
             details: format!("failed to set {}: {}", cell_ref, e),
         })?;
 
-    Ok(())
+    Ok((sheet, row, col))
+}
+
+/// Parse a range reference like "Inputs!B3:B40" and fill it with values from
+/// an inline list or a CSV file.
+///
+/// The data must have the same shape as the range; a single row or column of
+/// values may fill a range of the other orientation. Formula cells in the range
+/// are only overwritten when `replace_formulas` is set. Returns the cells set,
+/// as (sheet, row, col).
+fn apply_range_override(
+    workbook: &mut Workbook,
+    source: &Spreadsheet,
+    range_ref: &str,
+    data: &str,
+    sheet_names: &[String],
+    replace_formulas: bool,
+) -> Result<Vec<(String, u32, u32)>> {
+    use formualizer_workbook::LiteralValue;
+
+    let (sheet, cell) = split_sheet_prefix(range_ref, sheet_names)?;
+    let ((first_col, first_row), (last_col, last_row)) = parse_a1_range(cell)?;
+    let height = (last_row - first_row + 1) as usize;
+    let width = (last_col - first_col + 1) as usize;
+
+    let values = fit_range_shape(read_range_data(data)?, height, width).ok_or_else(|| {
+        Error::InvalidOverride(format!(
+            "{} is {}x{} but the data does not have that shape",
+            range_ref, height, width
+        ))
+    })?;
+
+    if !replace_formulas {
+        if let Some(ws) = source.get_sheet_by_name(&sheet) {
+            let formula_cell = (first_row..=last_row)
+                .flat_map(|r| (first_col..=last_col).map(move |c| (c, r)))
+                .find(|&pos| ws.get_cell(pos).is_some_and(|c| c.is_formula()));
+            if let Some((col, row)) = formula_cell {
+                return Err(Error::InvalidOverride(format!(
+                    "{} contains formulas (first at {}{}); use --replace-formulas to overwrite them",
+                    range_ref,
+                    column_letters(col),
+                    row
+                )));
+            }
+        }
+    }
+
+    let mut cells = Vec::with_capacity(height * width);
+    for (dr, row_values) in values.iter().enumerate() {
+        for (dc, value) in row_values.iter().enumerate() {
+            let literal = if value.is_empty() {
+                LiteralValue::Empty
+            } else {
+                parse_value(value)
+            };
+            let (row, col) = (first_row + dr as u32, first_col + dc as u32);
+            workbook
+                .set_value(&sheet, row, col, literal)
+                .map_err(|e| Error::InvalidOverride(format!("failed to set {}: {}", range_ref, e)))?;
+            cells.push((sheet.clone(), row, col));
+        }
+    }
+
+    Ok(cells)
+}
+
+/// Read range override data: `@path` loads a CSV file, anything else is an
+/// inline list with rows separated by `;` and columns by `,` (optionally
+/// wrapped in braces, like an Excel array constant).
+fn read_range_data(data: &str) -> Result<Vec<Vec<String>>> {
+    if let Some(path) = data.strip_prefix('@') {
+        let mut reader = csv::ReaderBuilder::new()
+            .has_headers(false)
+            .flexible(true)
+            .from_path(path)
+            .map_err(|e| Error::InvalidOverride(format!("failed to read {}: {}", path, e)))?;
+
+        return reader
+            .records()
+            .map(|record| {
+                record
+                    .map(|r| r.iter().map(|v| v.trim().to_string()).collect())
+                    .map_err(|e| Error::InvalidOverride(format!("failed to read {}: {}", path, e)))
+            })
+            .collect();
+    }
+
+    let data = data.trim();
+    let data = data
+        .strip_prefix('{')
+        .and_then(|d| d.strip_suffix('}'))
+        .unwrap_or(data);
+
+    Ok(data
+        .split(';')
+        .map(|row| row.split(',').map(|v| v.trim().to_string()).collect())
+        .collect())
+}
+
+/// Fit parsed data to a range of the given shape, transposing a single row or
+/// column when the range has the other orientation. Returns None when the
+/// shapes are incompatible.
+fn fit_range_shape(
+    mut values: Vec<Vec<String>>,
+    height: usize,
+    width: usize,
+) -> Option<Vec<Vec<String>>> {
+    // Drop trailing blank lines from CSV input
+    while values.last().is_some_and(|r| r.iter().all(|v| v.is_empty())) {
+        values.pop();
+    }
+
+    let rows = values.len();
+    let cols = values.first().map(|r| r.len()).unwrap_or(0);
+    let rectangular = values.iter().all(|r| r.len() == cols);
+
+    if rectangular && rows == height && cols == width {
+        return Some(values);
+    }
+
+    // A flat list filling a one-dimensional range of the other orientation
+    let flat: Vec<String> = if rows == 1 {
+        values.into_iter().next().unwrap_or_default()
+    } else if rectangular && cols == 1 {
+        values.into_iter().flatten().collect()
+    } else {
+        return None;
+    };
+
+    if height == 1 && flat.len() == width {
+        Some(vec![flat])
+    } else if width == 1 && flat.len() == height {
+        Some(flat.into_iter().map(|v| vec![v]).collect())
+    } else {
+        None
+    }
+}
+
+/// Convert column letters to a 1-based column number (A = 1, AA = 27).
+/// Returns None unless `letters` is one to three ASCII letters.
+pub(crate) fn column_number(letters: &str) -> Option<u32> {
+    let letters = letters.trim();
+    if letters.is_empty() || letters.len() > 3 || !letters.chars().all(|c| c.is_ascii_alphabetic()) {
+        return None;
+    }
+    Some(letters.chars().fold(0, |acc, c| {
+        acc * 26 + (c.to_ascii_uppercase() as u32 - 'A' as u32 + 1)
+    }))
+}
+
+/// Convert a 1-based column number to letters (1 = A, 27 = AA).
+pub(crate) fn column_letters(mut col: u32) -> String {
+    let mut letters = Vec::new();
+    while col > 0 {
+        let rem = (col - 1) % 26;
+        letters.push((b'A' + rem as u8) as char);
+        col = (col - 1) / 26;
+    }
+    letters.iter().rev().collect()
 }
 
 /// Parse a cell reference string into (sheet, col, row).
 fn parse_cell_reference(cell_ref: &str, sheet_names: &[String]) -> Result<(String, u32, u32)> {
-    let (sheet, cell) = if let Some(idx) = cell_ref.find('!') {
-        let sheet = &cell_ref[..idx];
-        let cell = &cell_ref[idx + 1..];
-        (sheet.to_string(), cell)
+    let (sheet, cell) = split_sheet_prefix(cell_ref, sheet_names)?;
+    let (col, row) = parse_a1_notation(cell)?;
+    Ok((sheet, col, row))
+}
+
+/// Split a "Sheet!A1" style reference into the sheet name and the cell part.
+/// Quoted sheet names ('My Sheet'!A1) are unquoted; without a sheet prefix
+/// the first sheet is used.
+fn split_sheet_prefix<'a>(reference: &'a str, sheet_names: &[String]) -> Result<(String, &'a str)> {
+    if let Some(idx) = reference.rfind('!') {
+        let sheet = &reference[..idx];
+        let cell = &reference[idx + 1..];
+        let sheet = sheet
+            .strip_prefix('\'')
+            .and_then(|s| s.strip_suffix('\''))
+            .map(|s| s.replace("''", "'"))
+            .unwrap_or_else(|| sheet.to_string());
+        Ok((sheet, cell))
     } else {
         // Use first sheet if not specified
         let sheet = sheet_names
             .first()
             .ok_or_else(|| Error::SheetNotFound {
                 name: "default".to_string(),
+                suggestion: String::new(),
                 available: String::new(),
             })?
             .clone();
-        (sheet, cell_ref)
+        Ok((sheet, reference))
+    }
+}
+
+/// A rectangular cell range as ((first_col, first_row), (last_col, last_row)).
+pub(crate) type CellRange = ((u32, u32), (u32, u32));
+
+/// Parse an A1-style range (e.g., "B3:B40") into its corners.
+/// A single cell reference is treated as a one-cell range.
+pub(crate) fn parse_a1_range(range: &str) -> Result<CellRange> {
+    let (start, end) = match range.split_once(':') {
+        Some((start, end)) => (parse_a1_notation(start)?, parse_a1_notation(end)?),
+        None => {
+            let cell = parse_a1_notation(range)?;
+            (cell, cell)
+        }
     };
 
-    let (col, row) = parse_a1_notation(cell)?;
-    Ok((sheet, col, row))
+    Ok((
+        (start.0.min(end.0), start.1.min(end.1)),
+        (start.0.max(end.0), start.1.max(end.1)),
+    ))
 }
 
 /// Parse A1 notation (e.g., "A1", "BC123") into (col, row).
-fn parse_a1_notation(cell: &str) -> Result<(u32, u32)> {
-    let cell = cell.trim().to_uppercase();
+pub(crate) fn parse_a1_notation(cell: &str) -> Result<(u32, u32)> {
+    let cell = cell.trim().replace('$', "").to_uppercase();
 
     // Find where letters end and digits begin
     let col_end = cell
@@ -229,9 +1000,10 @@ fn parse_a1_notation(cell: &str) -> Result<(u32, u32)> {
     let row_str = &cell[col_end..];
 
     // Convert column letters to number (A=1, B=2, ..., Z=26, AA=27, etc.)
-    let col: u32 = col_str.chars().fold(0, |acc, c| {
-        acc * 26 + (c as u32 - 'A' as u32 + 1)
-    });
+    let col = column_number(col_str).ok_or_else(|| Error::InvalidExcel {
+        path: std::path::PathBuf::new(),
+        details: format!("invalid column in cell reference: {}", cell),
+    })?;
 
     let row: u32 = row_str.parse().map_err(|_| Error::InvalidExcel {
         path: std::path::PathBuf::new(),
@@ -276,6 +1048,204 @@ fn parse_value(value: &str) -> formualizer_workbook::LiteralValue {
 mod tests {
     use super::*;
 
+    /// Write a one-sheet workbook filled in by `build` to a temporary file.
+    fn temp_workbook(name: &str, build: impl FnOnce(&mut umya_spreadsheet::Worksheet)) -> PathBuf {
+        let mut book = umya_spreadsheet::new_file();
+        build(book.get_sheet_mut(&0).unwrap());
+        let path = std::env::temp_dir().join(format!("excel2csv-{}-{}.xlsx", name, std::process::id()));
+        umya_spreadsheet::writer::xlsx::write(&book, &path).unwrap();
+        path
+    }
+
+    /// Store an array formula with the spill range Excel last recorded.
+    fn set_array_formula(sheet: &mut umya_spreadsheet::Worksheet, cell: &str, text: &str, reference: &str) {
+        let mut formula = umya_spreadsheet::CellFormula::default();
+        formula
+            .set_text(text)
+            .set_formula_type(umya_spreadsheet::CellFormulaValues::Array)
+            .set_reference(reference);
+        sheet.get_cell_mut(cell).get_cell_value_mut().set_formula_obj(formula);
+    }
+
+    #[test]
+    fn test_layout_spills() {
+        use formualizer_workbook::LiteralValue;
+
+        let path = temp_workbook("spills", |sheet| {
+            sheet.get_cell_mut("A1").set_formula("=SEQUENCE(2,2)");
+            // Obstructed by a value
+            sheet.get_cell_mut("A4").set_formula("=SEQUENCE(2)");
+            sheet.get_cell_mut("A5").set_value("x");
+            // Obstructed by a formula
+            sheet.get_cell_mut("D1").set_formula("=SEQUENCE(2)");
+            sheet.get_cell_mut("D2").set_formula("=1+1");
+            // Obstructed by the spill of B6, laid out first
+            sheet.get_cell_mut("B6").set_formula("=SEQUENCE(2)");
+            sheet.get_cell_mut("A7").set_formula("=SEQUENCE(1,3)");
+        });
+        let wb = EvaluatedWorkbook::load(&path, &EvalConfig::default()).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        let value = |row, col| {
+            wb.get_literal("Sheet1", row, col)
+                .map(|v| wb.format_value(v))
+                .unwrap_or_default()
+        };
+        let is_error = |row, col| matches!(wb.get_literal("Sheet1", row, col), Some(LiteralValue::Error(_)));
+
+        // Laid out into the neighbouring cells
+        assert_eq!([value(1, 1), value(1, 2), value(2, 1), value(2, 2)], ["1", "2", "3", "4"]);
+
+        assert!(is_error(4, 1));
+        assert_eq!(value(4, 1), "#SPILL!");
+        assert_eq!(value(5, 1), "x");
+
+        assert!(is_error(1, 4));
+        assert_eq!(value(2, 4), "2");
+
+        assert_eq!((value(6, 2), value(7, 2)), ("1".to_string(), "2".to_string()));
+        assert!(is_error(7, 1));
+        assert_eq!(value(7, 3), "");
+    }
+
+    #[test]
+    fn test_shrunk_spill_clears_stale_cells() {
+        let path = temp_workbook("shrunk", |sheet| {
+            set_array_formula(sheet, "A1", "SEQUENCE(2)", "A1:A4");
+            for (cell, value) in [("A2", 2), ("A3", 3), ("A4", 4)] {
+                sheet.get_cell_mut(cell).set_value_number(value);
+            }
+        });
+        let wb = EvaluatedWorkbook::load(&path, &EvalConfig::default()).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(wb.get_raw_value("Sheet1", 3, 1), None);
+        assert_eq!(wb.get_raw_value("Sheet1", 4, 1), None);
+
+        let config = crate::writer::CsvConfig::default();
+        let mut csv = Vec::new();
+        crate::writer::write_evaluated_sheet(&wb, "Sheet1", &mut csv, &config).unwrap();
+        assert_eq!(String::from_utf8(csv).unwrap(), "1\n2\n\"\"\n\"\"\n");
+    }
+
+    /// Mark a workbook written by umya as using the 1904 date system.
+    fn set_date1904(path: &Path) {
+        use std::io::{Read, Write};
+
+        let mut archive = zip::ZipArchive::new(std::fs::File::open(path).unwrap()).unwrap();
+        let mut parts = Vec::new();
+        for i in 0..archive.len() {
+            let mut part = archive.by_index(i).unwrap();
+            let mut data = Vec::new();
+            part.read_to_end(&mut data).unwrap();
+            parts.push((part.name().to_string(), data));
+        }
+
+        let mut zip = zip::ZipWriter::new(std::fs::File::create(path).unwrap());
+        for (name, data) in parts {
+            let data = match name.as_str() {
+                "xl/workbook.xml" => {
+                    let xml = String::from_utf8(data).unwrap();
+                    let xml = match xml.contains("<workbookPr") {
+                        true => xml.replacen("<workbookPr", "<workbookPr date1904=\"1\"", 1),
+                        false => xml.replacen("<sheets>", "<workbookPr date1904=\"1\"/><sheets>", 1),
+                    };
+                    xml.into_bytes()
+                }
+                _ => data,
+            };
+            zip.start_file(name, zip::write::SimpleFileOptions::default()).unwrap();
+            zip.write_all(&data).unwrap();
+        }
+        zip.finish().unwrap();
+    }
+
+    #[test]
+    fn test_recalculate_date1904() {
+        let path = temp_workbook("date1904-eval", |sheet| {
+            // 2024-03-15 as stored in a 1904 workbook
+            sheet.get_cell_mut("A1").set_value_number(43904);
+            sheet.get_cell_mut("B1").set_formula("=DATE(2024,3,15)");
+            sheet.get_cell_mut("C1").set_formula("=B1=A1");
+            sheet.get_cell_mut("D1").set_formula("=YEAR(A1)");
+        });
+        set_date1904(&path);
+        assert!(crate::reader::detect_date1904(&path).unwrap());
+
+        let config = EvalConfig {
+            date1904: true,
+            ..Default::default()
+        };
+        let evaluated = EvaluatedWorkbook::load(&path, &config).unwrap();
+        let sheet = evaluated.sheet_names()[0].clone();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(evaluated.get_raw_value(&sheet, 1, 2), Some(RawValue::Number(43904.0)));
+        assert_eq!(evaluated.get_raw_value(&sheet, 1, 3), Some(RawValue::Text("TRUE".to_string())));
+        assert_eq!(evaluated.get_raw_value(&sheet, 1, 4), Some(RawValue::Number(2024.0)));
+    }
+
+    #[test]
+    fn test_save_round_trip() {
+        let input = temp_workbook("save-in", |sheet| {
+            sheet.get_cell_mut("A1").set_value_number(2);
+            sheet.get_cell_mut("B1").set_formula("=A1*10");
+            // Grown spill: the file recorded two rows, SEQUENCE(3) needs three
+            set_array_formula(sheet, "C1", "SEQUENCE(3)", "C1:C2");
+            sheet.get_cell_mut("C2").set_value_number(2);
+            // Shrunk spill: the file recorded four rows, SEQUENCE(2) needs two
+            set_array_formula(sheet, "D1", "SEQUENCE(2)", "D1:D4");
+            for (cell, value) in [("D2", 2), ("D3", 3), ("D4", 4)] {
+                sheet.get_cell_mut(cell).set_value_number(value);
+            }
+            // Obstructed spill and a text result that looks like an error
+            sheet.get_cell_mut("E1").set_formula("=SEQUENCE(2)");
+            sheet.get_cell_mut("E2").set_value("x");
+            sheet.get_cell_mut("F1").set_formula("=\"#SPILL!\"");
+        });
+        let output = input.with_file_name(format!("excel2csv-save-out-{}.xlsx", std::process::id()));
+
+        let config = EvalConfig {
+            overrides: HashMap::from([("A1".to_string(), "3".to_string())]),
+            ..Default::default()
+        };
+        EvaluatedWorkbook::load(&input, &config).unwrap().save(&output).unwrap();
+
+        let book = umya_spreadsheet::reader::xlsx::read(&output).unwrap();
+        let sheet = book.get_sheet(&0).unwrap();
+        let value = |cell: &str| sheet.get_cell(cell).map(|c| c.get_value().to_string()).unwrap_or_default();
+        let reference = |cell: &str| {
+            sheet
+                .get_cell(cell)
+                .and_then(|c| c.get_formula_obj())
+                .map(|f| f.get_reference().to_string())
+                .unwrap_or_default()
+        };
+
+        assert_eq!(value("A1"), "3");
+        assert_eq!(value("B1"), "30");
+        assert!(sheet.get_cell("B1").unwrap().is_formula());
+
+        assert_eq!((value("C1"), value("C2"), value("C3")), ("1".into(), "2".into(), "3".into()));
+        assert_eq!(reference("C1"), "C1:C3");
+
+        assert_eq!((value("D1"), value("D2")), ("1".into(), "2".into()));
+        assert_eq!((value("D3"), value("D4")), (String::new(), String::new()));
+        assert_eq!(reference("D1"), "D1:D2");
+
+        let blocked = sheet.get_cell("E1").unwrap();
+        assert_eq!(blocked.get_data_type(), "e");
+        assert_eq!(blocked.get_value(), "#SPILL!");
+        assert_eq!(value("E2"), "x");
+
+        let text = sheet.get_cell("F1").unwrap();
+        assert_ne!(text.get_data_type(), "e");
+        assert_eq!(text.get_value(), "#SPILL!");
+
+        std::fs::remove_file(&input).unwrap();
+        std::fs::remove_file(&output).unwrap();
+    }
+
     #[test]
     fn test_parse_a1_notation() {
         assert_eq!(parse_a1_notation("A1").unwrap(), (1, 1));
@@ -283,6 +1253,48 @@ mod tests {
         assert_eq!(parse_a1_notation("Z1").unwrap(), (26, 1));
         assert_eq!(parse_a1_notation("AA1").unwrap(), (27, 1));
         assert_eq!(parse_a1_notation("AB10").unwrap(), (28, 10));
+        assert!(parse_a1_notation("A-1").is_err());
+        assert!(parse_a1_notation("A.1").is_err());
+        assert!(parse_a1_notation("ABCDEFGHIJKLMNOP1").is_err());
+        assert!(parse_a1_notation("12").is_err());
+    }
+
+    #[test]
+    fn test_parse_a1_range() {
+        assert_eq!(parse_a1_range("B3:B40").unwrap(), ((2, 3), (2, 40)));
+        assert_eq!(parse_a1_range("$A$1:$C$2").unwrap(), ((1, 1), (3, 2)));
+        assert_eq!(parse_a1_range("C2:A1").unwrap(), ((1, 1), (3, 2)));
+        assert_eq!(parse_a1_range("D4").unwrap(), ((4, 4), (4, 4)));
+    }
+
+    #[test]
+    fn test_fit_range_shape() {
+        let column = read_range_data("1,2,3").unwrap();
+        assert_eq!(
+            fit_range_shape(column, 3, 1).unwrap(),
+            vec![vec!["1"], vec!["2"], vec!["3"]]
+        );
+
+        let table = read_range_data("{1,2;3,4}").unwrap();
+        assert_eq!(
+            fit_range_shape(table.clone(), 2, 2).unwrap(),
+            vec![vec!["1", "2"], vec!["3", "4"]]
+        );
+        assert!(fit_range_shape(table, 4, 1).is_none());
+        assert!(fit_range_shape(read_range_data("1,2").unwrap(), 3, 1).is_none());
+    }
+
+    #[test]
+    fn test_split_sheet_prefix() {
+        let sheets = vec!["Sheet1".to_string()];
+        assert_eq!(
+            split_sheet_prefix("'My Sheet'!B3:B40", &sheets).unwrap(),
+            ("My Sheet".to_string(), "B3:B40")
+        );
+        assert_eq!(
+            split_sheet_prefix("A1", &sheets).unwrap(),
+            ("Sheet1".to_string(), "A1")
+        );
     }
 
     #[test]
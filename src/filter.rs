@@ -0,0 +1,485 @@
+//! Row filter expressions for `--where`.
+//!
+//! Expressions compare header-named columns against literals or other
+//! columns, using the cells' typed values rather than their formatted text:
+//!
+//! ```text
+//! Amount >= 1000 AND (Region = 'East' OR Region = 'West')
+//! [Invoice Date] < '2024-07-01' AND NOT Customer ~ '^(?i)test'
+//! Notes IS EMPTY OR Status != 'closed'
+//! ```
+//!
+//! Columns are bare words or `[bracketed names]`; strings use single or
+//! double quotes. Operators are `=`, `!=` (`<>`), `<`, `<=`, `>`, `>=`, `~`
+//! and `!~` (regex match), `IS [NOT] EMPTY`, and `AND`/`OR`/`NOT` (or `&&`,
+//! `||`, `!`). A date literal compared with a numeric cell is converted to
+//! its date serial number. Comparisons involving an empty cell are false,
+//! except `!=`.
+
+use crate::columns::{find_header, HeaderMatch};
+use crate::error::{Error, Result};
+use crate::writer::{CellData, RawValue};
+use chrono::{NaiveDate, NaiveDateTime};
+use regex::Regex;
+use std::cmp::Ordering;
+use std::collections::HashMap;
+
+/// A parsed `--where` expression.
+#[derive(Debug)]
+pub struct RowFilter {
+    expr: Expr,
+    matching: HeaderMatch,
+}
+
+#[derive(Debug)]
+enum Expr {
+    And(Box<Expr>, Box<Expr>),
+    Or(Box<Expr>, Box<Expr>),
+    Not(Box<Expr>),
+    Compare(Operand, CmpOp, Operand),
+    Matches(Operand, Regex),
+    IsEmpty(Operand),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Operand {
+    Column(String),
+    Number(f64),
+    Text(String),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum CmpOp {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+/// A cell value as seen by comparisons.
+#[derive(Debug, Clone, Copy)]
+enum Value<'a> {
+    Empty,
+    Number(f64),
+    Text(&'a str),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    LParen,
+    RParen,
+    Op(&'static str),
+    Word(String),
+    Column(String),
+    Text(String),
+    Number(f64),
+}
+
+impl RowFilter {
+    /// Parse an expression; columns are matched against headers with
+    /// `matching` when the filter is applied.
+    pub fn parse(source: &str, matching: HeaderMatch) -> Result<Self> {
+        let tokens = tokenize(source).map_err(|e| invalid(source, e))?;
+        let mut parser = Parser { tokens, pos: 0 };
+        let expr = parser.or().map_err(|e| invalid(source, e))?;
+        if let Some(token) = parser.tokens.get(parser.pos) {
+            return Err(invalid(source, format!("unexpected {:?}", token)));
+        }
+        Ok(RowFilter { expr, matching })
+    }
+
+    /// Drop the rows below the header (the first row) that do not match.
+    pub fn apply(&self, sheet_name: &str, rows: &mut Vec<Vec<CellData>>, date1904: bool) -> Result<()> {
+        let headers: Vec<String> = rows
+            .first()
+            .map(|r| r.iter().map(|c| c.text.trim().to_string()).collect())
+            .unwrap_or_default();
+
+        let mut names = Vec::new();
+        self.expr.column_names(&mut names);
+        let mut columns = HashMap::new();
+        for name in names {
+            let idx = find_header(name, &headers, self.matching).ok_or_else(|| Error::ColumnNotFound {
+                name: name.to_string(),
+                sheet: sheet_name.to_string(),
+                available: headers
+                    .iter()
+                    .filter(|h| !h.is_empty())
+                    .cloned()
+                    .collect::<Vec<_>>()
+                    .join(", "),
+            })?;
+            columns.insert(name.to_string(), idx);
+        }
+
+        let mut index = 0;
+        rows.retain(|row| {
+            index += 1;
+            index == 1 || self.expr.eval(row, &columns, date1904)
+        });
+        Ok(())
+    }
+}
+
+fn invalid(source: &str, details: String) -> Error {
+    Error::InvalidArgument(format!("invalid --where expression \"{}\": {}", source, details))
+}
+
+impl Expr {
+    fn column_names<'a>(&'a self, names: &mut Vec<&'a str>) {
+        let operands: Vec<&Operand> = match self {
+            Expr::And(a, b) | Expr::Or(a, b) => {
+                a.column_names(names);
+                b.column_names(names);
+                return;
+            }
+            Expr::Not(e) => return e.column_names(names),
+            Expr::Compare(a, _, b) => vec![a, b],
+            Expr::Matches(a, _) | Expr::IsEmpty(a) => vec![a],
+        };
+        for operand in operands {
+            if let Operand::Column(name) = operand {
+                names.push(name);
+            }
+        }
+    }
+
+    fn eval(&self, row: &[CellData], columns: &HashMap<String, usize>, date1904: bool) -> bool {
+        let value = |operand| value(operand, row, columns);
+
+        match self {
+            Expr::And(a, b) => a.eval(row, columns, date1904) && b.eval(row, columns, date1904),
+            Expr::Or(a, b) => a.eval(row, columns, date1904) || b.eval(row, columns, date1904),
+            Expr::Not(e) => !e.eval(row, columns, date1904),
+            Expr::Compare(a, op, b) => {
+                let ordering = compare(value(a), value(b), date1904);
+                match (op, ordering) {
+                    (CmpOp::Ne, None) => true,
+                    (_, None) => false,
+                    (CmpOp::Eq, Some(o)) => o == Ordering::Equal,
+                    (CmpOp::Ne, Some(o)) => o != Ordering::Equal,
+                    (CmpOp::Lt, Some(o)) => o == Ordering::Less,
+                    (CmpOp::Le, Some(o)) => o != Ordering::Greater,
+                    (CmpOp::Gt, Some(o)) => o == Ordering::Greater,
+                    (CmpOp::Ge, Some(o)) => o != Ordering::Less,
+                }
+            }
+            Expr::Matches(a, regex) => match value(a) {
+                Value::Empty => false,
+                Value::Number(n) => regex.is_match(&n.to_string()),
+                Value::Text(t) => regex.is_match(t),
+            },
+            Expr::IsEmpty(a) => matches!(value(a), Value::Empty),
+        }
+    }
+}
+
+/// The value of an operand in a row.
+fn value<'a>(operand: &'a Operand, row: &'a [CellData], columns: &HashMap<String, usize>) -> Value<'a> {
+    match operand {
+        Operand::Number(n) => Value::Number(*n),
+        Operand::Text(t) => Value::Text(t),
+        Operand::Column(name) => match row.get(columns[name]).and_then(|c| c.raw.as_ref()) {
+            Some(RawValue::Number(n)) => Value::Number(*n),
+            Some(RawValue::Text(t)) if !t.is_empty() => Value::Text(t),
+            _ => Value::Empty,
+        },
+    }
+}
+
+/// Compare two values: numbers numerically, text against numbers as a number
+/// or date, and text against text as strings. None if either is empty or the
+/// two cannot be compared.
+fn compare(a: Value, b: Value, date1904: bool) -> Option<Ordering> {
+    match (a, b) {
+        (Value::Empty, _) | (_, Value::Empty) => None,
+        (Value::Number(x), Value::Number(y)) => x.partial_cmp(&y),
+        (Value::Number(x), Value::Text(t)) => text_to_number(t, date1904).and_then(|y| x.partial_cmp(&y)),
+        (Value::Text(t), Value::Number(y)) => text_to_number(t, date1904).and_then(|x| x.partial_cmp(&y)),
+        (Value::Text(s), Value::Text(t)) => Some(s.cmp(t)),
+    }
+}
+
+/// Read text as a number, or as a date/datetime converted to a serial number.
+fn text_to_number(text: &str, date1904: bool) -> Option<f64> {
+    const LAYOUTS: [&str; 3] = ["%Y-%m-%d %H:%M:%S", "%Y-%m-%dT%H:%M:%S", "%Y-%m-%d %H:%M"];
+
+    let text = text.trim();
+    if let Ok(n) = text.parse() {
+        return Some(n);
+    }
+    let datetime = LAYOUTS
+        .iter()
+        .find_map(|layout| NaiveDateTime::parse_from_str(text, layout).ok())
+        .or_else(|| NaiveDate::parse_from_str(text, "%Y-%m-%d").ok()?.and_hms_opt(0, 0, 0))?;
+    Some(datetime_to_serial(datetime, date1904))
+}
+
+/// Convert a date and time to an Excel serial number.
+fn datetime_to_serial(datetime: NaiveDateTime, date1904: bool) -> f64 {
+    let epoch = if date1904 {
+        NaiveDate::from_ymd_opt(1904, 1, 1)
+    } else if datetime.date() < NaiveDate::from_ymd_opt(1900, 3, 1).unwrap_or_default() {
+        // Before Excel's phantom 1900-02-29
+        NaiveDate::from_ymd_opt(1899, 12, 31)
+    } else {
+        NaiveDate::from_ymd_opt(1899, 12, 30)
+    }
+    .and_then(|d| d.and_hms_opt(0, 0, 0))
+    .unwrap_or_default();
+
+    (datetime - epoch).num_milliseconds() as f64 / 86_400_000.0
+}
+
+fn tokenize(source: &str) -> std::result::Result<Vec<Token>, String> {
+    const OPERATORS: [&str; 14] = ["==", "!=", "<>", "<=", ">=", "!~", "&&", "||", "=", "<", ">", "~", "!", "-"];
+
+    let chars: Vec<char> = source.chars().collect();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+
+    while i < chars.len() {
+        let c = chars[i];
+        let rest: String = chars[i..].iter().take(2).collect();
+
+        if c.is_whitespace() {
+            i += 1;
+        } else if c == '(' || c == ')' {
+            tokens.push(if c == '(' { Token::LParen } else { Token::RParen });
+            i += 1;
+        } else if c == '\'' || c == '"' {
+            // Quotes are escaped by doubling them
+            let mut text = String::new();
+            i += 1;
+            loop {
+                match chars.get(i) {
+                    None => return Err("unterminated string".to_string()),
+                    Some(&q) if q == c && chars.get(i + 1) == Some(&c) => {
+                        text.push(c);
+                        i += 2;
+                    }
+                    Some(&q) if q == c => {
+                        i += 1;
+                        break;
+                    }
+                    Some(&ch) => {
+                        text.push(ch);
+                        i += 1;
+                    }
+                }
+            }
+            tokens.push(Token::Text(text));
+        } else if c == '[' {
+            let end = chars[i..]
+                .iter()
+                .position(|&ch| ch == ']')
+                .ok_or("unterminated [column name]")?;
+            tokens.push(Token::Column(chars[i + 1..i + end].iter().collect::<String>().trim().to_string()));
+            i += end + 1;
+        } else if c.is_ascii_digit()
+            || (c == '-' && chars.get(i + 1).is_some_and(|d| d.is_ascii_digit() || *d == '.'))
+            || (c == '.' && chars.get(i + 1).is_some_and(|d| d.is_ascii_digit()))
+        {
+            let start = i;
+            i += 1;
+            while i < chars.len() && (chars[i].is_ascii_digit() || chars[i] == '.') {
+                i += 1;
+            }
+            let literal: String = chars[start..i].iter().collect();
+            let number = literal.parse().map_err(|_| format!("invalid number {}", literal))?;
+            tokens.push(Token::Number(number));
+        } else if c.is_alphabetic() || c == '_' {
+            let start = i;
+            while i < chars.len() && (chars[i].is_alphanumeric() || chars[i] == '_' || chars[i] == '.') {
+                i += 1;
+            }
+            tokens.push(Token::Word(chars[start..i].iter().collect()));
+        } else if let Some(op) = OPERATORS.iter().find(|op| rest.starts_with(*op)) {
+            if *op == "-" {
+                return Err("unexpected '-'".to_string());
+            }
+            tokens.push(Token::Op(op));
+            i += op.len();
+        } else {
+            return Err(format!("unexpected '{}'", c));
+        }
+    }
+
+    Ok(tokens)
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
+        token
+    }
+
+    /// Consume a keyword (case-insensitive) or one of the given operators.
+    fn eat(&mut self, keyword: &str, ops: &[&str]) -> bool {
+        let matched = match self.peek() {
+            Some(Token::Word(w)) => w.eq_ignore_ascii_case(keyword),
+            Some(Token::Op(op)) => ops.contains(op),
+            _ => false,
+        };
+        if matched {
+            self.pos += 1;
+        }
+        matched
+    }
+
+    fn or(&mut self) -> std::result::Result<Expr, String> {
+        let mut expr = self.and()?;
+        while self.eat("OR", &["||"]) {
+            expr = Expr::Or(Box::new(expr), Box::new(self.and()?));
+        }
+        Ok(expr)
+    }
+
+    fn and(&mut self) -> std::result::Result<Expr, String> {
+        let mut expr = self.not()?;
+        while self.eat("AND", &["&&"]) {
+            expr = Expr::And(Box::new(expr), Box::new(self.not()?));
+        }
+        Ok(expr)
+    }
+
+    fn not(&mut self) -> std::result::Result<Expr, String> {
+        if self.eat("NOT", &["!"]) {
+            return Ok(Expr::Not(Box::new(self.not()?)));
+        }
+        self.condition()
+    }
+
+    fn condition(&mut self) -> std::result::Result<Expr, String> {
+        if self.peek() == Some(&Token::LParen) {
+            self.pos += 1;
+            let expr = self.or()?;
+            if self.next() != Some(Token::RParen) {
+                return Err("missing ')'".to_string());
+            }
+            return Ok(expr);
+        }
+
+        let left = self.operand()?;
+
+        if self.eat("IS", &[]) {
+            let negate = self.eat("NOT", &[]);
+            if !self.eat("EMPTY", &[]) {
+                return Err("expected EMPTY after IS".to_string());
+            }
+            let expr = Expr::IsEmpty(left);
+            return Ok(if negate { Expr::Not(Box::new(expr)) } else { expr });
+        }
+
+        let op = match self.next() {
+            Some(Token::Op(op)) => op,
+            other => return Err(format!("expected an operator, found {:?}", other)),
+        };
+
+        if op == "~" || op == "!~" {
+            let pattern = match self.next() {
+                Some(Token::Text(pattern)) => pattern,
+                other => return Err(format!("expected a quoted regex, found {:?}", other)),
+            };
+            let regex = Regex::new(&pattern).map_err(|e| e.to_string())?;
+            let expr = Expr::Matches(left, regex);
+            return Ok(if op == "!~" { Expr::Not(Box::new(expr)) } else { expr });
+        }
+
+        let op = match op {
+            "=" | "==" => CmpOp::Eq,
+            "!=" | "<>" => CmpOp::Ne,
+            "<" => CmpOp::Lt,
+            "<=" => CmpOp::Le,
+            ">" => CmpOp::Gt,
+            ">=" => CmpOp::Ge,
+            other => return Err(format!("unexpected operator {}", other)),
+        };
+        Ok(Expr::Compare(left, op, self.operand()?))
+    }
+
+    fn operand(&mut self) -> std::result::Result<Operand, String> {
+        match self.next() {
+            Some(Token::Number(n)) => Ok(Operand::Number(n)),
+            Some(Token::Text(t)) => Ok(Operand::Text(t)),
+            Some(Token::Column(name)) => Ok(Operand::Column(name)),
+            // Excel stores booleans as TRUE/FALSE text
+            Some(Token::Word(w)) if w.eq_ignore_ascii_case("true") || w.eq_ignore_ascii_case("false") => {
+                Ok(Operand::Text(w.to_uppercase()))
+            }
+            Some(Token::Word(w)) => Ok(Operand::Column(w)),
+            other => Err(format!("expected a column or value, found {:?}", other)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn row(values: &[Option<RawValue>]) -> Vec<CellData> {
+        values
+            .iter()
+            .map(|raw| CellData {
+                raw: raw.clone(),
+                ..Default::default()
+            })
+            .collect()
+    }
+
+    fn eval(source: &str, values: &[Option<RawValue>]) -> bool {
+        let filter = RowFilter::parse(source, HeaderMatch::Exact).unwrap();
+        let columns: HashMap<String, usize> = [("Region", 0), ("Amount", 1), ("Invoice Date", 2), ("Notes", 3)]
+            .iter()
+            .map(|(name, idx)| (name.to_string(), *idx))
+            .collect();
+        filter.expr.eval(&row(values), &columns, false)
+    }
+
+    #[test]
+    fn test_eval() {
+        let east = [
+            Some(RawValue::Text("East".to_string())),
+            Some(RawValue::Number(1500.0)),
+            Some(RawValue::Number(45474.0)), // 2024-07-01
+            None,
+        ];
+
+        assert!(eval("Amount >= 1000 AND Region = 'East'", &east));
+        assert!(!eval("Amount > 1500 or Region = \"West\"", &east));
+        assert!(eval("NOT (Region = 'West') && Notes IS EMPTY", &east));
+        assert!(!eval("Notes IS NOT EMPTY", &east));
+        assert!(eval("[Invoice Date] >= '2024-07-01' AND [Invoice Date] < '2024-07-02'", &east));
+        assert!(eval("Region ~ '^(?i)ea' AND Region !~ 'st$x'", &east));
+        assert!(eval("Notes != 'x'", &east));
+        assert!(!eval("Notes = 'x' OR Notes < 5", &east));
+        assert!(eval("Amount = -5 OR Amount = 1500", &east));
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        for source in ["Amount >", "Amount = 'x", "(Amount = 1", "Amount ~ 5", "Region ~ '('", "Amount ! 3"] {
+            assert!(RowFilter::parse(source, HeaderMatch::Exact).is_err(), "{}", source);
+        }
+    }
+
+    #[test]
+    fn test_datetime_to_serial() {
+        let date = |s| NaiveDate::parse_from_str(s, "%Y-%m-%d").unwrap().and_hms_opt(0, 0, 0).unwrap();
+        assert_eq!(datetime_to_serial(date("2024-07-01"), false), 45474.0);
+        assert_eq!(datetime_to_serial(date("1900-01-01"), false), 1.0);
+        assert_eq!(datetime_to_serial(date("1904-01-02"), true), 1.0);
+    }
+}
@@ -0,0 +1,201 @@
+//! Excel's "General" number format.
+//!
+//! ssfmt parses "General" as literal characters and formats numbers as
+//! "Gnral" (see `ssfmt-bug-report.md`), so General is formatted here instead.
+//! The rules follow what Excel shows in a standard-width column: at most 11
+//! characters (12 for negative numbers), up to 10 significant digits for
+//! fractions, scientific notation for very large and very small magnitudes,
+//! and no trailing zeros.
+
+/// Maximum display width of a General-formatted number, excluding the sign.
+const WIDTH: usize = 11;
+
+/// Whether a format code is Excel's built-in General format.
+pub fn is_general(format_code: &str) -> bool {
+    format_code.trim().eq_ignore_ascii_case("General")
+}
+
+/// Format a number the way Excel's General format displays it.
+pub fn format_general(value: f64) -> String {
+    if value == 0.0 {
+        return "0".to_string();
+    }
+    if !value.is_finite() {
+        return "#NUM!".to_string();
+    }
+
+    // Whole numbers that fit the column are shown in full
+    if value.fract() == 0.0 && value.abs() < 1e11 {
+        return format!("{}", value as i64);
+    }
+
+    let magnitude = value.abs().log10().floor() as i32;
+    let formatted = if (-4..=-1).contains(&magnitude) {
+        to_precision(value, (10 + magnitude) as usize)
+    } else if magnitude.abs() <= 9 {
+        fit_fixed(value)
+    } else if magnitude == 10 {
+        // Eleven integer digits fill the width, so round to a whole number;
+        // rounding up to 1e11 carries into scientific notation
+        to_precision(value.round(), WIDTH)
+    } else {
+        fit_large_or_small(value)
+    };
+
+    strip_trailing_zeros(&normalize_exponent(&formatted.to_uppercase()))
+}
+
+/// Numbers between 1e-9 and 1e10: as many decimals as fit the width, falling
+/// back to 10 significant digits, then scientific notation.
+fn fit_fixed(value: f64) -> String {
+    let width = if value < 0.0 { WIDTH + 1 } else { WIDTH };
+
+    let fixed = strip_trailing_zeros(&format!("{:.12}", value));
+    if fixed.len() <= width {
+        return fixed;
+    }
+
+    let precise = to_precision(value, 10);
+    if precise.len() <= width {
+        return precise;
+    }
+
+    to_exponential(value, 5)
+}
+
+/// Numbers of 1e11 and above or below 1e-4: fixed notation if it fits,
+/// otherwise 6 significant digits (usually scientific).
+fn fit_large_or_small(value: f64) -> String {
+    let width = if value < 0.0 { WIDTH + 1 } else { WIDTH };
+
+    // Tiny values can round to zero in fixed notation; those need scientific
+    let fixed = strip_trailing_zeros(&format!("{:.11}", value));
+    if fixed.len() <= width && fixed.trim_start_matches('-') != "0" {
+        return fixed;
+    }
+
+    to_precision(value, 6)
+}
+
+/// Format with `precision` significant digits, switching to scientific
+/// notation for exponents below -6 or at least `precision` (like
+/// JavaScript's `Number.prototype.toPrecision`).
+fn to_precision(value: f64, precision: usize) -> String {
+    let precision = precision.max(1);
+    let scientific = format!("{:.*e}", precision - 1, value);
+    let exponent: i32 = scientific
+        .rsplit_once('e')
+        .and_then(|(_, e)| e.parse().ok())
+        .unwrap_or(0);
+
+    if exponent < -6 || exponent >= precision as i32 {
+        return to_exponential(value, precision - 1);
+    }
+
+    let decimals = (precision as i32 - 1 - exponent).max(0) as usize;
+    format!("{:.*}", decimals, value)
+}
+
+/// Format in scientific notation with `decimals` mantissa digits and a signed
+/// exponent (e.g. "1.23457e+11").
+fn to_exponential(value: f64, decimals: usize) -> String {
+    let scientific = format!("{:.*e}", decimals, value);
+    match scientific.split_once('e') {
+        Some((mantissa, exponent)) if !exponent.starts_with('-') => {
+            format!("{}e+{}", mantissa, exponent)
+        }
+        Some((mantissa, exponent)) => format!("{}e{}", mantissa, exponent),
+        None => scientific,
+    }
+}
+
+/// Drop trailing zeros from the mantissa of scientific notation and pad the
+/// exponent to two digits ("1.50000E+5" becomes "1.5E+05").
+fn normalize_exponent(formatted: &str) -> String {
+    let Some((mantissa, exponent)) = formatted.split_once('E') else {
+        return formatted.to_string();
+    };
+
+    let mantissa = strip_trailing_zeros(mantissa);
+    let (sign, digits) = match exponent.chars().next() {
+        Some(c @ ('+' | '-')) => (c, &exponent[1..]),
+        _ => ('+', exponent),
+    };
+
+    format!("{}E{}{:0>2}", mantissa, sign, digits)
+}
+
+/// Drop trailing zeros after the decimal point, and the point itself.
+fn strip_trailing_zeros(formatted: &str) -> String {
+    if !formatted.contains('.') || formatted.contains(['e', 'E']) {
+        return formatted.to_string();
+    }
+    formatted
+        .trim_end_matches('0')
+        .trim_end_matches('.')
+        .to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Expected outputs as displayed by Excel in a standard-width column.
+    #[test]
+    fn test_format_general_matches_excel() {
+        let cases: &[(f64, &str)] = &[
+            (0.0, "0"),
+            (1.0, "1"),
+            (-1.0, "-1"),
+            (10.0, "10"),
+            (100000.0, "100000"),
+            (2147483648.0, "2147483648"),
+            (12345678901.0, "12345678901"),
+            (12345678901.4, "12345678901"),
+            (12345678901.99, "12345678902"),
+            (12345678900.5, "12345678901"),
+            (-12345678901.5, "-12345678902"),
+            (99999999999.5, "1E+11"),
+            (-99999999999.5, "-1E+11"),
+            (123456789012.0, "1.23457E+11"),
+            (1e15, "1E+15"),
+            (-1e15, "-1E+15"),
+            (1e20, "1E+20"),
+            (0.1, "0.1"),
+            (0.1 + 0.2, "0.3"),
+            (0.5, "0.5"),
+            (1234.5, "1234.5"),
+            (123.456, "123.456"),
+            (1.0 / 3.0, "0.333333333"),
+            (2.0 / 3.0, "0.666666667"),
+            (-2.0 / 3.0, "-0.666666667"),
+            (1234567.891, "1234567.891"),
+            (std::f64::consts::PI, "3.141592654"),
+            (-std::f64::consts::PI, "-3.141592654"),
+            (0.0001, "0.0001"),
+            (-0.000123, "-0.000123"),
+            (0.00001, "0.00001"),
+            (1e-10, "1E-10"),
+            (1.5e-12, "1.5E-12"),
+            (123456.789012345, "123456.789"),
+        ];
+
+        for &(value, expected) in cases {
+            assert_eq!(format_general(value), expected, "formatting {}", value);
+        }
+    }
+
+    #[test]
+    fn test_is_general() {
+        assert!(is_general("General"));
+        assert!(is_general("general"));
+        assert!(!is_general("0.00"));
+    }
+
+    #[test]
+    fn test_to_precision() {
+        assert_eq!(to_precision(0.000123, 6), "0.000123000");
+        assert_eq!(to_precision(123456789012.0, 6), "1.23457e+11");
+        assert_eq!(to_precision(1e-10, 6), "1.00000e-10");
+    }
+}
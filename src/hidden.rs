@@ -0,0 +1,148 @@
+//! Hidden rows, columns and sheets.
+//!
+//! Visibility is read from the worksheet's row and column dimensions and the
+//! sheet state recorded in the workbook ("hidden" or "veryHidden", the latter
+//! only reachable from VBA). A [`HiddenFilter`] either skips hidden items or,
+//! for auditing, keeps nothing but them.
+
+use clap::ValueEnum;
+use std::collections::BTreeSet;
+use umya_spreadsheet::structs::Worksheet;
+
+/// Kind of item that can be hidden.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Hidden {
+    Rows,
+    Cols,
+    Sheets,
+}
+
+/// Which hidden items to drop (or keep exclusively).
+#[derive(Debug, Clone, Default)]
+pub struct HiddenFilter {
+    pub kinds: Vec<Hidden>,
+    /// Keep only the hidden items of `kinds` instead of skipping them.
+    pub only_hidden: bool,
+}
+
+/// Hidden rows and columns of a sheet, 1-based.
+#[derive(Debug, Clone, Default)]
+pub struct SheetVisibility {
+    pub rows: BTreeSet<u32>,
+    pub cols: BTreeSet<u32>,
+}
+
+impl SheetVisibility {
+    /// Read hidden rows and columns from a worksheet's dimensions.
+    pub fn of(sheet: &Worksheet) -> Self {
+        let rows = sheet
+            .get_row_dimensions()
+            .iter()
+            .filter(|row| *row.get_hidden())
+            .map(|row| *row.get_row_num())
+            .collect();
+        let cols = sheet
+            .get_column_dimensions()
+            .iter()
+            .filter(|col| *col.get_hidden())
+            .map(|col| *col.get_col_num())
+            .collect();
+        SheetVisibility { rows, cols }
+    }
+}
+
+/// The sheet state if it is not visible: "hidden" or "veryHidden".
+pub fn sheet_state(sheet: &Worksheet) -> Option<&str> {
+    match sheet.get_sheet_state() {
+        "" | "visible" => None,
+        state => Some(state),
+    }
+}
+
+impl HiddenFilter {
+    pub fn is_active(&self) -> bool {
+        !self.kinds.is_empty()
+    }
+
+    /// Whether an item of `kind` with the given visibility is exported.
+    pub fn keep(&self, kind: Hidden, hidden: bool) -> bool {
+        !self.kinds.contains(&kind) || hidden == self.only_hidden
+    }
+
+    /// Clear the entries of a per-row keep mask for rows the filter excludes.
+    pub fn mark_rows(&self, visibility: &SheetVisibility, keep: &mut [bool]) {
+        for (i, keep) in keep.iter_mut().enumerate() {
+            *keep &= self.keep(Hidden::Rows, visibility.rows.contains(&(i as u32 + 1)));
+        }
+    }
+
+    /// Clear the entries of a per-column keep mask for columns the filter
+    /// excludes.
+    pub fn mark_cols(&self, visibility: &SheetVisibility, keep: &mut [bool]) {
+        for (i, keep) in keep.iter_mut().enumerate() {
+            *keep &= self.keep(Hidden::Cols, visibility.cols.contains(&(i as u32 + 1)));
+        }
+    }
+
+    /// Describe what the filter drops from a sheet, for verbose output.
+    pub fn describe(&self, visibility: &SheetVisibility) -> Vec<String> {
+        let verb = if self.only_hidden { "kept only" } else { "skipped" };
+        let mut notes = Vec::new();
+
+        if self.kinds.contains(&Hidden::Rows) && !visibility.rows.is_empty() {
+            let rows: Vec<String> = visibility.rows.iter().map(|r| r.to_string()).collect();
+            notes.push(format!("{} hidden rows: {}", verb, rows.join(", ")));
+        }
+        if self.kinds.contains(&Hidden::Cols) && !visibility.cols.is_empty() {
+            let cols: Vec<String> = visibility
+                .cols
+                .iter()
+                .map(|&c| crate::evaluator::column_letters(c))
+                .collect();
+            notes.push(format!("{} hidden columns: {}", verb, cols.join(", ")));
+        }
+
+        notes
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_mark_rows() {
+        let visibility = SheetVisibility {
+            rows: BTreeSet::from([2]),
+            cols: BTreeSet::new(),
+        };
+
+        let skip = HiddenFilter { kinds: vec![Hidden::Rows], only_hidden: false };
+        let mut keep = vec![true, true, false];
+        skip.mark_rows(&visibility, &mut keep);
+        assert_eq!(keep, vec![true, false, false]);
+
+        let only = HiddenFilter { kinds: vec![Hidden::Rows], only_hidden: true };
+        let mut keep = vec![true; 3];
+        only.mark_rows(&visibility, &mut keep);
+        assert_eq!(keep, vec![false, true, false]);
+    }
+
+    #[test]
+    fn test_mark_cols() {
+        let visibility = SheetVisibility {
+            rows: BTreeSet::new(),
+            cols: BTreeSet::from([1]),
+        };
+
+        let skip = HiddenFilter { kinds: vec![Hidden::Cols], only_hidden: false };
+        let mut keep = vec![true; 2];
+        skip.mark_cols(&visibility, &mut keep);
+        assert_eq!(keep, vec![false, true]);
+
+        // Rows are not affected by a columns-only filter
+        let mut keep = vec![true; 2];
+        skip.mark_rows(&SheetVisibility { rows: BTreeSet::from([1]), cols: BTreeSet::new() }, &mut keep);
+        assert_eq!(keep, vec![true, true]);
+    }
+}
@@ -0,0 +1,123 @@
+//! Hyperlink export.
+//!
+//! Cells can link to URLs or other locations either through the worksheet's
+//! hyperlink collection or through a `HYPERLINK(url, text)` formula. Either
+//! way the exported text is only the display text unless a [`HyperlinkMode`]
+//! asks for the target.
+
+use clap::ValueEnum;
+
+/// How to export cells that carry a hyperlink.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, ValueEnum)]
+pub enum HyperlinkMode {
+    /// The display text only
+    #[default]
+    Text,
+    /// The link target in place of the text
+    Url,
+    /// The text followed by the target in parentheses
+    Both,
+    /// A Markdown link, [text](url)
+    Markdown,
+}
+
+impl HyperlinkMode {
+    /// Render a cell's text with its hyperlink target.
+    pub fn render(&self, text: &str, url: &str) -> String {
+        match self {
+            HyperlinkMode::Text => text.to_string(),
+            HyperlinkMode::Url => url.to_string(),
+            HyperlinkMode::Both if text.is_empty() || text == url => url.to_string(),
+            HyperlinkMode::Both => format!("{} ({})", text, url),
+            HyperlinkMode::Markdown => {
+                let label = if text.is_empty() { url } else { text };
+                format!(
+                    "[{}]({})",
+                    label.replace('[', "\\[").replace(']', "\\]"),
+                    url.replace(' ', "%20").replace(')', "%29")
+                )
+            }
+        }
+    }
+}
+
+/// The link target argument of a `HYPERLINK()` formula.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FormulaTarget {
+    /// A string literal URL.
+    Literal(String),
+    /// A reference to a cell on the same sheet holding the URL, as (col, row).
+    Cell(u32, u32),
+}
+
+/// Extract the target of a formula of the form `HYPERLINK(target, ...)`.
+///
+/// Only string literals and single-cell references are recognised; targets
+/// built from expressions return None.
+pub fn formula_target(formula: &str) -> Option<FormulaTarget> {
+    let formula = formula.trim().trim_start_matches('=').trim_start();
+    let prefix = formula.get(..10)?;
+    if !prefix.eq_ignore_ascii_case("HYPERLINK(") {
+        return None;
+    }
+
+    let args = &formula[10..];
+    let first = first_argument(args)?.trim();
+
+    if let Some(literal) = first.strip_prefix('"').and_then(|s| s.strip_suffix('"')) {
+        return Some(FormulaTarget::Literal(literal.replace("\"\"", "\"")));
+    }
+
+    let reference = first.replace('$', "");
+    let split = reference.find(|c: char| c.is_ascii_digit())?;
+    let (letters, digits) = reference.split_at(split);
+    let col = crate::evaluator::column_number(letters)?;
+    let row = digits.parse().ok()?;
+    Some(FormulaTarget::Cell(col, row))
+}
+
+/// The first argument of a function call, given the text after the `(`.
+fn first_argument(args: &str) -> Option<&str> {
+    let mut in_string = false;
+    let mut depth = 0usize;
+
+    for (i, c) in args.char_indices() {
+        match c {
+            '"' => in_string = !in_string,
+            '(' if !in_string => depth += 1,
+            ')' if !in_string && depth == 0 => return Some(&args[..i]),
+            ')' if !in_string => depth -= 1,
+            ',' if !in_string && depth == 0 => return Some(&args[..i]),
+            _ => {}
+        }
+    }
+
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_formula_target() {
+        assert_eq!(
+            formula_target("HYPERLINK(\"https://example.com/a?b=1,2\", \"Ticket\")"),
+            Some(FormulaTarget::Literal("https://example.com/a?b=1,2".to_string()))
+        );
+        assert_eq!(
+            formula_target("=hyperlink($B$2)"),
+            Some(FormulaTarget::Cell(2, 2))
+        );
+        assert_eq!(formula_target("HYPERLINK(\"http://x/\"&A1, \"x\")"), None);
+        assert_eq!(formula_target("SUM(A1:A2)"), None);
+    }
+
+    #[test]
+    fn test_render() {
+        let url = "https://example.com/T-1";
+        assert_eq!(HyperlinkMode::Url.render("T-1", url), url);
+        assert_eq!(HyperlinkMode::Both.render("T-1", url), "T-1 (https://example.com/T-1)");
+        assert_eq!(HyperlinkMode::Markdown.render("T-1", url), "[T-1](https://example.com/T-1)");
+    }
+}
@@ -0,0 +1,519 @@
+//! External workbook links.
+//!
+//! Formulas can reference cells in other workbooks (`='[rates.xlsx]Curve'!B4`).
+//! XLSX files record each linked workbook in an `externalLink` part together
+//! with the values Excel last saw in it; stored formulas refer to the link by
+//! its 1-based position (`[1]Curve!B4`). This module reads those parts,
+//! locates the linked files on disk and rewrites formulas to point at sheets
+//! the evaluator can load.
+
+use crate::error::{Error, Result};
+use quick_xml::events::{BytesStart, Event};
+use quick_xml::Reader;
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+/// A workbook referenced from formulas in the workbook being converted.
+#[derive(Debug, Clone)]
+pub struct ExternalLink {
+    /// 1-based index used by stored formulas (`[1]Curve!B4`).
+    pub index: usize,
+    /// Path of the linked workbook as recorded by Excel.
+    pub target: String,
+    /// Sheet names of the linked workbook, in order.
+    pub sheet_names: Vec<String>,
+    /// Values Excel cached for the linked workbook, keyed by (sheet, row, col).
+    pub cached: HashMap<(String, u32, u32), CachedValue>,
+}
+
+/// A cell value cached in an `externalLink` part.
+#[derive(Debug, Clone, PartialEq)]
+pub enum CachedValue {
+    Number(f64),
+    Text(String),
+    Boolean(bool),
+    Error(String),
+}
+
+impl ExternalLink {
+    /// File name of the linked workbook (e.g. "rates.xlsx").
+    pub fn file_name(&self) -> &str {
+        self.target
+            .rsplit(['/', '\\'])
+            .next()
+            .unwrap_or(&self.target)
+    }
+
+    /// Whether a name from a formula or `--link` mapping refers to this link.
+    /// Matches the file name with or without its extension, ignoring case.
+    pub fn matches(&self, name: &str) -> bool {
+        let file_name = self.file_name();
+        let stem = file_name
+            .rsplit_once('.')
+            .map(|(stem, _)| stem)
+            .unwrap_or(file_name);
+        name.eq_ignore_ascii_case(file_name) || name.eq_ignore_ascii_case(stem)
+    }
+}
+
+/// Read the external links recorded in an XLSX file.
+///
+/// Returns an empty list for formats that do not store links (e.g. XLS).
+pub fn read_external_links(path: &Path) -> Result<Vec<ExternalLink>> {
+    let extension = path
+        .extension()
+        .and_then(|e| e.to_str())
+        .map(|e| e.to_lowercase())
+        .unwrap_or_default();
+    if !matches!(extension.as_str(), "xlsx" | "xlsm") {
+        return Ok(Vec::new());
+    }
+
+    let invalid = |details: String| Error::InvalidExcel {
+        path: path.to_path_buf(),
+        details,
+    };
+
+    let file = File::open(path)?;
+    let mut archive = zip::ZipArchive::new(file).map_err(|e| invalid(e.to_string()))?;
+
+    let Some(workbook_xml) = read_part(&mut archive, "xl/workbook.xml") else {
+        return Ok(Vec::new());
+    };
+    let rel_ids = external_reference_ids(&workbook_xml).map_err(invalid)?;
+    if rel_ids.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let workbook_rels = read_part(&mut archive, "xl/_rels/workbook.xml.rels").unwrap_or_default();
+    let rel_targets = relationship_targets(&workbook_rels).map_err(invalid)?;
+
+    let mut links = Vec::new();
+    for (i, rel_id) in rel_ids.iter().enumerate() {
+        let Some(part_target) = rel_targets.get(rel_id) else {
+            continue;
+        };
+        let part = resolve_part_path("xl", part_target);
+        let Some(part_xml) = read_part(&mut archive, &part) else {
+            continue;
+        };
+
+        let (dir, file) = part.rsplit_once('/').unwrap_or(("", part.as_str()));
+        let part_rels = read_part(&mut archive, &format!("{}/_rels/{}.rels", dir, file))
+            .unwrap_or_default();
+        let target = relationship_targets(&part_rels)
+            .map_err(invalid)?
+            .into_values()
+            .next()
+            .unwrap_or_default();
+
+        let (sheet_names, cached) = parse_external_book(&part_xml).map_err(invalid)?;
+        links.push(ExternalLink {
+            index: i + 1,
+            target,
+            sheet_names,
+            cached,
+        });
+    }
+
+    Ok(links)
+}
+
+/// Locate a linked workbook on disk.
+///
+/// `--link name=path` mappings take precedence, followed by the path Excel
+/// recorded, followed by a file of the same name next to the input workbook.
+pub fn resolve_link_path(
+    link: &ExternalLink,
+    base_dir: &Path,
+    mappings: &HashMap<String, PathBuf>,
+) -> Option<PathBuf> {
+    if let Some((_, path)) = mappings.iter().find(|(name, _)| link.matches(name)) {
+        return Some(path.clone());
+    }
+
+    let recorded = link
+        .target
+        .strip_prefix("file:///")
+        .unwrap_or(&link.target);
+    let recorded = PathBuf::from(recorded);
+    if recorded.is_absolute() && recorded.exists() {
+        return Some(recorded);
+    }
+
+    let sibling = base_dir.join(link.file_name());
+    sibling.exists().then_some(sibling)
+}
+
+/// Name of the sheet holding a linked workbook's sheet inside the evaluator.
+pub fn linked_sheet_name(link: &ExternalLink, sheet: &str) -> String {
+    format!("__link{}_{}", link.index, sheet)
+}
+
+/// Rewrite external references in a formula to point at linked sheets.
+///
+/// Both the stored form (`[1]Curve!B4`) and the displayed form
+/// (`'[rates.xlsx]Curve'!B4`) are recognised; `resolve` maps the bracketed
+/// book and the sheet name to the replacement sheet name. References that
+/// `resolve` does not know are left untouched.
+pub fn rewrite_external_refs<F>(formula: &str, resolve: F) -> String
+where
+    F: Fn(&str, &str) -> Option<String>,
+{
+    let chars: Vec<char> = formula.chars().collect();
+    let mut out = String::with_capacity(formula.len());
+    let mut i = 0;
+
+    while i < chars.len() {
+        let c = chars[i];
+
+        // Copy string literals verbatim
+        if c == '"' {
+            let start = i;
+            i += 1;
+            while i < chars.len() {
+                if chars[i] == '"' {
+                    if chars.get(i + 1) == Some(&'"') {
+                        i += 2;
+                        continue;
+                    }
+                    break;
+                }
+                i += 1;
+            }
+            i = (i + 1).min(chars.len());
+            out.extend(&chars[start..i]);
+            continue;
+        }
+
+        if let Some((book, sheet, end)) = match_external_ref(&chars, i) {
+            if let Some(replacement) = resolve(&book, &sheet) {
+                out.push('\'');
+                out.push_str(&replacement.replace('\'', "''"));
+                out.push_str("'!");
+                i = end;
+                continue;
+            }
+        }
+
+        out.push(c);
+        i += 1;
+    }
+
+    out
+}
+
+/// Match an external reference prefix (`[book]Sheet!` or `'[book]Sheet'!`)
+/// starting at `start`, returning (book, sheet, index after the `!`).
+fn match_external_ref(chars: &[char], start: usize) -> Option<(String, String, usize)> {
+    // A bracket following an identifier is a structured reference (Table1[Col])
+    if start > 0 {
+        let prev = chars[start - 1];
+        if prev.is_alphanumeric() || prev == '_' || prev == ']' {
+            return None;
+        }
+    }
+
+    let quoted = chars[start] == '\'';
+    let mut i = if quoted { start + 1 } else { start };
+    if chars.get(i) != Some(&'[') {
+        return None;
+    }
+    i += 1;
+
+    let book_start = i;
+    while i < chars.len() && chars[i] != ']' {
+        i += 1;
+    }
+    if i >= chars.len() {
+        return None;
+    }
+    let book: String = chars[book_start..i].iter().collect();
+    i += 1;
+
+    let mut sheet = String::new();
+    if quoted {
+        loop {
+            match chars.get(i) {
+                Some('\'') if chars.get(i + 1) == Some(&'\'') => {
+                    sheet.push('\'');
+                    i += 2;
+                }
+                Some('\'') => {
+                    i += 1;
+                    break;
+                }
+                Some(&c) => {
+                    sheet.push(c);
+                    i += 1;
+                }
+                None => return None,
+            }
+        }
+    } else {
+        while i < chars.len() && (chars[i].is_alphanumeric() || chars[i] == '_' || chars[i] == '.') {
+            sheet.push(chars[i]);
+            i += 1;
+        }
+    }
+
+    if sheet.is_empty() || chars.get(i) != Some(&'!') {
+        return None;
+    }
+
+    Some((book, sheet, i + 1))
+}
+
+/// Read a part from the XLSX archive as a string.
+pub(crate) fn read_part(archive: &mut zip::ZipArchive<File>, name: &str) -> Option<String> {
+    let mut entry = archive.by_name(name).ok()?;
+    let mut content = String::new();
+    entry.read_to_string(&mut content).ok()?;
+    Some(content)
+}
+
+/// Resolve a relationship target relative to the directory of its source part.
+pub(crate) fn resolve_part_path(base_dir: &str, target: &str) -> String {
+    if let Some(absolute) = target.strip_prefix('/') {
+        return absolute.to_string();
+    }
+
+    let mut parts: Vec<&str> = base_dir.split('/').filter(|p| !p.is_empty()).collect();
+    for segment in target.split('/') {
+        match segment {
+            ".." => {
+                parts.pop();
+            }
+            "." | "" => {}
+            s => parts.push(s),
+        }
+    }
+    parts.join("/")
+}
+
+/// Get an attribute value by local name.
+pub(crate) fn attribute(element: &BytesStart, name: &[u8]) -> Option<String> {
+    element
+        .attributes()
+        .flatten()
+        .find(|a| a.key.local_name().as_ref() == name)
+        .and_then(|a| a.unescape_value().ok())
+        .map(|v| v.into_owned())
+}
+
+/// Relationship ids of `<externalReference>` elements in workbook.xml, in order.
+fn external_reference_ids(xml: &str) -> std::result::Result<Vec<String>, String> {
+    let mut reader = Reader::from_str(xml);
+    let mut ids = Vec::new();
+
+    loop {
+        match reader.read_event() {
+            Ok(Event::Start(e)) | Ok(Event::Empty(e))
+                if e.local_name().as_ref() == b"externalReference" =>
+            {
+                if let Some(id) = attribute(&e, b"id") {
+                    ids.push(id);
+                }
+            }
+            Ok(Event::Eof) => break,
+            Err(e) => return Err(format!("workbook.xml: {}", e)),
+            _ => {}
+        }
+    }
+
+    Ok(ids)
+}
+
+/// Map of relationship id to target from a `.rels` part.
+pub(crate) fn relationship_targets(xml: &str) -> std::result::Result<HashMap<String, String>, String> {
+    let mut reader = Reader::from_str(xml);
+    let mut targets = HashMap::new();
+
+    loop {
+        match reader.read_event() {
+            Ok(Event::Start(e)) | Ok(Event::Empty(e))
+                if e.local_name().as_ref() == b"Relationship" =>
+            {
+                if let (Some(id), Some(target)) = (attribute(&e, b"Id"), attribute(&e, b"Target")) {
+                    targets.insert(id, target);
+                }
+            }
+            Ok(Event::Eof) => break,
+            Err(e) => return Err(format!("relationships: {}", e)),
+            _ => {}
+        }
+    }
+
+    Ok(targets)
+}
+
+/// Parse an `externalLink` part into its sheet names and cached values.
+#[allow(clippy::type_complexity)]
+fn parse_external_book(
+    xml: &str,
+) -> std::result::Result<(Vec<String>, HashMap<(String, u32, u32), CachedValue>), String> {
+    let mut reader = Reader::from_str(xml);
+    let mut sheet_names = Vec::new();
+    let mut cached = HashMap::new();
+
+    let mut sheet: Option<String> = None;
+    let mut cell: Option<(u32, u32, String)> = None;
+    let mut in_value = false;
+    let mut text = String::new();
+
+    loop {
+        match reader.read_event() {
+            Ok(Event::Start(e)) | Ok(Event::Empty(e)) => match e.local_name().as_ref() {
+                b"sheetName" => {
+                    if let Some(name) = attribute(&e, b"val") {
+                        sheet_names.push(name);
+                    }
+                }
+                b"sheetData" => {
+                    sheet = attribute(&e, b"sheetId")
+                        .and_then(|id| id.parse::<usize>().ok())
+                        .and_then(|id| sheet_names.get(id).cloned());
+                }
+                b"cell" => {
+                    cell = attribute(&e, b"r")
+                        .and_then(|r| crate::evaluator::parse_a1_notation(&r).ok())
+                        .map(|(col, row)| (col, row, attribute(&e, b"t").unwrap_or_default()));
+                }
+                b"v" => {
+                    in_value = true;
+                    text.clear();
+                }
+                _ => {}
+            },
+            Ok(Event::Text(t)) if in_value => {
+                text.push_str(&t.unescape().map_err(|e| e.to_string())?);
+            }
+            Ok(Event::End(e)) => match e.local_name().as_ref() {
+                b"v" => {
+                    in_value = false;
+                    if let (Some(sheet), Some((col, row, kind))) = (&sheet, &cell) {
+                        let value = match kind.as_str() {
+                            "str" | "s" | "inlineStr" => CachedValue::Text(text.clone()),
+                            "b" => CachedValue::Boolean(text == "1"),
+                            "e" => CachedValue::Error(text.clone()),
+                            _ => match text.parse::<f64>() {
+                                Ok(n) => CachedValue::Number(n),
+                                Err(_) => CachedValue::Text(text.clone()),
+                            },
+                        };
+                        cached.insert((sheet.clone(), *row, *col), value);
+                    }
+                }
+                b"cell" => cell = None,
+                b"sheetData" => sheet = None,
+                _ => {}
+            },
+            Ok(Event::Eof) => break,
+            Err(e) => return Err(format!("externalLink: {}", e)),
+            _ => {}
+        }
+    }
+
+    Ok((sheet_names, cached))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn resolve(book: &str, sheet: &str) -> Option<String> {
+        (book == "1" || book == "rates.xlsx").then(|| format!("__link1_{}", sheet))
+    }
+
+    #[test]
+    fn test_rewrite_external_refs() {
+        assert_eq!(
+            rewrite_external_refs("[1]Curve!B4*2", resolve),
+            "'__link1_Curve'!B4*2"
+        );
+        assert_eq!(
+            rewrite_external_refs("SUM('[rates.xlsx]Spot Rates'!B4:B10)", resolve),
+            "SUM('__link1_Spot Rates'!B4:B10)"
+        );
+        assert_eq!(
+            rewrite_external_refs("\"[1]Curve!B4\"&Table1[Col]", resolve),
+            "\"[1]Curve!B4\"&Table1[Col]"
+        );
+        assert_eq!(rewrite_external_refs("[2]Curve!B4", resolve), "[2]Curve!B4");
+    }
+
+    fn link(target: &str) -> ExternalLink {
+        ExternalLink {
+            index: 1,
+            target: target.to_string(),
+            sheet_names: vec!["Curve".to_string()],
+            cached: HashMap::new(),
+        }
+    }
+
+    #[test]
+    fn test_parse_external_book() {
+        let xml = r#"<externalLink xmlns="http://schemas.openxmlformats.org/spreadsheetml/2006/main">
+            <externalBook r:id="rId1" xmlns:r="http://schemas.openxmlformats.org/officeDocument/2006/relationships">
+                <sheetNames><sheetName val="Curve"/><sheetName val="Spot Rates"/></sheetNames>
+                <sheetDataSet>
+                    <sheetData sheetId="0">
+                        <row r="4"><cell r="B4"><v>0.0525</v></cell><cell r="C4" t="str"><v>AA &amp; B</v></cell></row>
+                    </sheetData>
+                    <sheetData sheetId="1">
+                        <row r="2"><cell r="A2" t="b"><v>1</v></cell><cell r="$AB$10" t="e"><v>#REF!</v></cell></row>
+                    </sheetData>
+                </sheetDataSet>
+            </externalBook>
+        </externalLink>"#;
+
+        let (sheet_names, cached) = parse_external_book(xml).unwrap();
+        assert_eq!(sheet_names, vec!["Curve", "Spot Rates"]);
+        assert_eq!(cached.len(), 4);
+
+        let get = |sheet: &str, row, col| cached.get(&(sheet.to_string(), row, col));
+        assert_eq!(get("Curve", 4, 2), Some(&CachedValue::Number(0.0525)));
+        assert_eq!(get("Curve", 4, 3), Some(&CachedValue::Text("AA & B".to_string())));
+        assert_eq!(get("Spot Rates", 2, 1), Some(&CachedValue::Boolean(true)));
+        assert_eq!(get("Spot Rates", 10, 28), Some(&CachedValue::Error("#REF!".to_string())));
+    }
+
+    #[test]
+    fn test_resolve_link_path() {
+        let dir = std::env::temp_dir().join(format!("excel2csv-links-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let sibling = dir.join("rates.xlsx");
+        File::create(&sibling).unwrap();
+
+        // The recorded path does not exist, so the sibling file is used
+        let recorded = link("file:///nowhere/rates.xlsx");
+        assert_eq!(resolve_link_path(&recorded, &dir, &HashMap::new()), Some(sibling.clone()));
+
+        // A mapping wins over the sibling, matched with or without extension
+        let mapped = PathBuf::from("/data/rates-2024.xlsx");
+        let mappings = HashMap::from([("RATES".to_string(), mapped.clone())]);
+        assert_eq!(resolve_link_path(&recorded, &dir, &mappings), Some(mapped));
+
+        // An absolute recorded path that exists is used as is
+        let absolute = link(sibling.to_str().unwrap());
+        assert_eq!(resolve_link_path(&absolute, Path::new("/nowhere"), &HashMap::new()), Some(sibling));
+
+        assert_eq!(resolve_link_path(&link("missing.xlsx"), &dir, &HashMap::new()), None);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_resolve_part_path() {
+        assert_eq!(
+            resolve_part_path("xl", "externalLinks/externalLink1.xml"),
+            "xl/externalLinks/externalLink1.xml"
+        );
+        assert_eq!(
+            resolve_part_path("xl", "/xl/externalLinks/externalLink2.xml"),
+            "xl/externalLinks/externalLink2.xml"
+        );
+    }
+}
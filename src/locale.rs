@@ -0,0 +1,519 @@
+//! Locale settings for number and date formatting.
+//!
+//! ssfmt formats values the way en-US Excel displays them. A [`Locale`]
+//! adapts the format code before formatting, and the number placeholders'
+//! output after it: month and day name placeholders (`mmm`, `dddd`) become
+//! the locale's names, decimal and thousands separators are swapped, built-in
+//! currency formats get the locale's symbol, and the locale-dependent
+//! built-in date formats (IDs 14 and 22) are replaced. Literal text in the
+//! format code is never touched. Format codes can also select a locale of
+//! their own with a `[$-407]` style tag.
+
+use chrono::{Datelike, NaiveDateTime};
+use std::borrow::Cow;
+
+/// Formatting conventions of a locale.
+#[derive(Debug)]
+pub struct Locale {
+    /// BCP 47 tag, e.g. "de-DE".
+    pub tag: &'static str,
+    /// Windows locale id, as used in `[$-407]` format tags.
+    pub lcid: u32,
+    pub decimal_separator: char,
+    pub thousands_separator: char,
+    pub months: [&'static str; 12],
+    pub months_short: [&'static str; 12],
+    pub days: [&'static str; 7],
+    pub days_short: [&'static str; 7],
+    /// Format code for built-in format 14 (short date).
+    pub short_date: &'static str,
+    /// Format code for built-in format 22 (short date and time).
+    pub date_time: &'static str,
+    /// Currency symbol used by built-in currency formats.
+    pub currency: &'static str,
+}
+
+const EN_MONTHS: [&str; 12] = [
+    "January", "February", "March", "April", "May", "June", "July", "August", "September",
+    "October", "November", "December",
+];
+const EN_MONTHS_SHORT: [&str; 12] = [
+    "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+];
+const EN_DAYS: [&str; 7] = [
+    "Sunday", "Monday", "Tuesday", "Wednesday", "Thursday", "Friday", "Saturday",
+];
+const EN_DAYS_SHORT: [&str; 7] = ["Sun", "Mon", "Tue", "Wed", "Thu", "Fri", "Sat"];
+
+pub static EN_US: Locale = Locale {
+    tag: "en-US",
+    lcid: 0x409,
+    decimal_separator: '.',
+    thousands_separator: ',',
+    months: EN_MONTHS,
+    months_short: EN_MONTHS_SHORT,
+    days: EN_DAYS,
+    days_short: EN_DAYS_SHORT,
+    short_date: "m/d/yyyy",
+    date_time: "m/d/yyyy h:mm",
+    currency: "$",
+};
+
+static EN_GB: Locale = Locale {
+    tag: "en-GB",
+    lcid: 0x809,
+    decimal_separator: '.',
+    thousands_separator: ',',
+    months: EN_MONTHS,
+    months_short: EN_MONTHS_SHORT,
+    days: EN_DAYS,
+    days_short: EN_DAYS_SHORT,
+    short_date: "dd/mm/yyyy",
+    date_time: "dd/mm/yyyy hh:mm",
+    currency: "£",
+};
+
+static DE_DE: Locale = Locale {
+    tag: "de-DE",
+    lcid: 0x407,
+    decimal_separator: ',',
+    thousands_separator: '.',
+    months: [
+        "Januar", "Februar", "März", "April", "Mai", "Juni", "Juli", "August", "September",
+        "Oktober", "November", "Dezember",
+    ],
+    months_short: [
+        "Jan", "Feb", "Mär", "Apr", "Mai", "Jun", "Jul", "Aug", "Sep", "Okt", "Nov", "Dez",
+    ],
+    days: [
+        "Sonntag", "Montag", "Dienstag", "Mittwoch", "Donnerstag", "Freitag", "Samstag",
+    ],
+    days_short: ["So", "Mo", "Di", "Mi", "Do", "Fr", "Sa"],
+    short_date: "dd.mm.yyyy",
+    date_time: "dd.mm.yyyy hh:mm",
+    currency: "€",
+};
+
+static FR_FR: Locale = Locale {
+    tag: "fr-FR",
+    lcid: 0x40C,
+    decimal_separator: ',',
+    thousands_separator: '\u{a0}',
+    months: [
+        "janvier", "février", "mars", "avril", "mai", "juin", "juillet", "août", "septembre",
+        "octobre", "novembre", "décembre",
+    ],
+    months_short: [
+        "janv.", "févr.", "mars", "avr.", "mai", "juin", "juil.", "août", "sept.", "oct.",
+        "nov.", "déc.",
+    ],
+    days: [
+        "dimanche", "lundi", "mardi", "mercredi", "jeudi", "vendredi", "samedi",
+    ],
+    days_short: ["dim.", "lun.", "mar.", "mer.", "jeu.", "ven.", "sam."],
+    short_date: "dd/mm/yyyy",
+    date_time: "dd/mm/yyyy hh:mm",
+    currency: "€",
+};
+
+static ES_ES: Locale = Locale {
+    tag: "es-ES",
+    lcid: 0xC0A,
+    decimal_separator: ',',
+    thousands_separator: '.',
+    months: [
+        "enero", "febrero", "marzo", "abril", "mayo", "junio", "julio", "agosto", "septiembre",
+        "octubre", "noviembre", "diciembre",
+    ],
+    months_short: [
+        "ene", "feb", "mar", "abr", "may", "jun", "jul", "ago", "sept", "oct", "nov", "dic",
+    ],
+    days: [
+        "domingo", "lunes", "martes", "miércoles", "jueves", "viernes", "sábado",
+    ],
+    days_short: ["dom", "lun", "mar", "mié", "jue", "vie", "sáb"],
+    short_date: "dd/mm/yyyy",
+    date_time: "dd/mm/yyyy h:mm",
+    currency: "€",
+};
+
+static IT_IT: Locale = Locale {
+    tag: "it-IT",
+    lcid: 0x410,
+    decimal_separator: ',',
+    thousands_separator: '.',
+    months: [
+        "gennaio", "febbraio", "marzo", "aprile", "maggio", "giugno", "luglio", "agosto",
+        "settembre", "ottobre", "novembre", "dicembre",
+    ],
+    months_short: [
+        "gen", "feb", "mar", "apr", "mag", "giu", "lug", "ago", "set", "ott", "nov", "dic",
+    ],
+    days: [
+        "domenica", "lunedì", "martedì", "mercoledì", "giovedì", "venerdì", "sabato",
+    ],
+    days_short: ["dom", "lun", "mar", "mer", "gio", "ven", "sab"],
+    short_date: "dd/mm/yyyy",
+    date_time: "dd/mm/yyyy hh:mm",
+    currency: "€",
+};
+
+static NL_NL: Locale = Locale {
+    tag: "nl-NL",
+    lcid: 0x413,
+    decimal_separator: ',',
+    thousands_separator: '.',
+    months: [
+        "januari", "februari", "maart", "april", "mei", "juni", "juli", "augustus",
+        "september", "oktober", "november", "december",
+    ],
+    months_short: [
+        "jan", "feb", "mrt", "apr", "mei", "jun", "jul", "aug", "sep", "okt", "nov", "dec",
+    ],
+    days: [
+        "zondag", "maandag", "dinsdag", "woensdag", "donderdag", "vrijdag", "zaterdag",
+    ],
+    days_short: ["zo", "ma", "di", "wo", "do", "vr", "za"],
+    short_date: "d-m-yyyy",
+    date_time: "d-m-yyyy hh:mm",
+    currency: "€",
+};
+
+static JA_JP: Locale = Locale {
+    tag: "ja-JP",
+    lcid: 0x411,
+    decimal_separator: '.',
+    thousands_separator: ',',
+    months: [
+        "1月", "2月", "3月", "4月", "5月", "6月", "7月", "8月", "9月", "10月", "11月", "12月",
+    ],
+    months_short: [
+        "1月", "2月", "3月", "4月", "5月", "6月", "7月", "8月", "9月", "10月", "11月", "12月",
+    ],
+    days: [
+        "日曜日", "月曜日", "火曜日", "水曜日", "木曜日", "金曜日", "土曜日",
+    ],
+    days_short: ["日", "月", "火", "水", "木", "金", "土"],
+    short_date: "yyyy/m/d",
+    date_time: "yyyy/m/d h:mm",
+    currency: "¥",
+};
+
+static LOCALES: [&Locale; 8] = [&EN_US, &EN_GB, &DE_DE, &FR_FR, &ES_ES, &IT_IT, &NL_NL, &JA_JP];
+
+/// Built-in format IDs whose code contains the locale's currency symbol.
+const CURRENCY_FORMAT_IDS: [u32; 6] = [5, 6, 7, 8, 42, 44];
+
+impl Locale {
+    /// Look up a locale by tag (e.g. "de-DE", "de_de" or "de").
+    pub fn parse(tag: &str) -> Option<&'static Locale> {
+        let tag = tag.replace('_', "-");
+        LOCALES
+            .iter()
+            .find(|l| l.tag.eq_ignore_ascii_case(&tag))
+            .or_else(|| {
+                LOCALES
+                    .iter()
+                    .find(|l| l.tag.split('-').next().is_some_and(|lang| lang.eq_ignore_ascii_case(&tag)))
+            })
+            .copied()
+    }
+
+    /// Look up a locale by Windows locale id (e.g. 0x407 for de-DE).
+    pub fn from_lcid(lcid: u32) -> Option<&'static Locale> {
+        LOCALES.iter().find(|l| l.lcid == lcid).copied()
+    }
+
+    /// Whether this is the en-US locale ssfmt formats in.
+    fn is_default(&self) -> bool {
+        std::ptr::eq(self, &EN_US)
+    }
+
+    /// Tags of all supported locales, for error messages.
+    pub fn supported() -> String {
+        LOCALES.iter().map(|l| l.tag).collect::<Vec<_>>().join(", ")
+    }
+
+    /// Prepare a format code for this locale.
+    ///
+    /// Returns the locale that applies to the cell (a `[$-407]` tag in the code
+    /// overrides `self`) and the code to hand to ssfmt: locale tags are removed,
+    /// `[$€-407]` currency tags become quoted literals, and, for locales other
+    /// than en-US, locale-dependent built-in formats are replaced with the
+    /// locale's variants.
+    pub fn resolve_format<'a>(&'static self, id: u32, code: &'a str) -> (&'static Locale, Cow<'a, str>) {
+        let (tag_locale, code) = strip_locale_tags(code);
+        let locale = tag_locale.unwrap_or(self);
+
+        let code = match id {
+            14 if !locale.is_default() => Cow::Borrowed(locale.short_date),
+            22 if !locale.is_default() => Cow::Borrowed(locale.date_time),
+            id if CURRENCY_FORMAT_IDS.contains(&id) && locale.currency != "$" => {
+                Cow::Owned(code.replace('$', locale.currency))
+            }
+            _ => code,
+        };
+
+        (locale, code)
+    }
+
+    /// Format a number with `format`, which applies an en-US format code, and
+    /// give the result this locale's separators. Literal text in the code
+    /// (quoted or escaped) is passed through unchanged.
+    pub fn format_number(&self, code: &str, format: impl FnOnce(&str) -> String) -> String {
+        if self.is_default() {
+            return format(code);
+        }
+
+        let (protected, literals) = protect_literals(code);
+        let formatted = self.localize_number(&format(&protected));
+        formatted
+            .chars()
+            .map(|c| match literal_index(c) {
+                Some(i) if i < literals.len() => literals[i].clone(),
+                _ => c.to_string(),
+            })
+            .collect()
+    }
+
+    /// Give a number formatted in en-US conventions (without literal text)
+    /// this locale's separators.
+    pub fn localize_number(&self, formatted: &str) -> String {
+        let chars: Vec<char> = formatted.chars().collect();
+        let mut out = String::with_capacity(formatted.len());
+
+        for (i, &c) in chars.iter().enumerate() {
+            let prev_digit = i > 0 && chars[i - 1].is_ascii_digit();
+            let next_digit = chars.get(i + 1).is_some_and(|c| c.is_ascii_digit());
+            match c {
+                ',' if prev_digit && next_digit => out.push(self.thousands_separator),
+                '.' if next_digit => out.push(self.decimal_separator),
+                c => out.push(c),
+            }
+        }
+
+        out
+    }
+
+    /// Replace the month and day name placeholders of a date format code
+    /// (`mmm`, `mmmm`, `mmmmm`, `ddd`, `dddd`) with this locale's names for
+    /// `date`, as quoted literals. Returns None when the code needs no change.
+    pub fn localize_date_code(&self, code: &str, date: NaiveDateTime) -> Option<String> {
+        if self.is_default() {
+            return None;
+        }
+
+        let month = self.months[date.month0() as usize];
+        let month_short = self.months_short[date.month0() as usize];
+        let weekday = date.weekday().num_days_from_sunday() as usize;
+
+        let chars: Vec<char> = code.chars().collect();
+        let mut out = String::with_capacity(code.len());
+        let mut changed = false;
+        let mut i = 0;
+        while i < chars.len() {
+            let c = chars[i];
+            let run = chars[i..].iter().take_while(|&&r| r.eq_ignore_ascii_case(&c)).count();
+            let name = match c.to_ascii_lowercase() {
+                'm' if run == 3 => Some(month_short),
+                'm' if run == 5 => month.get(..month.chars().next().map_or(0, char::len_utf8)),
+                'm' if run > 3 => Some(month),
+                'd' if run == 3 => Some(self.days_short[weekday]),
+                'd' if run > 3 => Some(self.days[weekday]),
+                _ => None,
+            };
+            if let Some(name) = name {
+                out.push('"');
+                out.push_str(name);
+                out.push('"');
+                changed = true;
+                i += run;
+                continue;
+            }
+
+            // Copy literals and bracketed sections as they are
+            let end = match c {
+                '"' => chars[i + 1..].iter().position(|&q| q == '"').map_or(chars.len(), |p| i + p + 2),
+                '[' => chars[i..].iter().position(|&q| q == ']').map_or(chars.len(), |p| i + p + 1),
+                '\\' | '_' | '*' => (i + 2).min(chars.len()),
+                _ => i + run,
+            };
+            out.extend(&chars[i..end]);
+            i = end;
+        }
+
+        changed.then_some(out)
+    }
+
+    /// Replace the month and day name specifiers of a strftime format (`%B`,
+    /// `%b`, `%A`, `%a`) with this locale's names for `date`.
+    pub fn localize_strftime(&self, format: &str, date: NaiveDateTime) -> String {
+        if self.is_default() {
+            return format.to_string();
+        }
+
+        let weekday = date.weekday().num_days_from_sunday() as usize;
+        let mut out = String::with_capacity(format.len());
+        let mut chars = format.chars();
+        while let Some(c) = chars.next() {
+            if c != '%' {
+                out.push(c);
+                continue;
+            }
+            let spec = chars.next();
+            let name = match spec {
+                Some('B') => self.months[date.month0() as usize],
+                Some('b' | 'h') => self.months_short[date.month0() as usize],
+                Some('A') => self.days[weekday],
+                Some('a') => self.days_short[weekday],
+                _ => {
+                    out.push('%');
+                    out.extend(spec);
+                    continue;
+                }
+            };
+            out.push_str(&name.replace('%', "%%"));
+        }
+        out
+    }
+}
+
+/// First private-use character, standing in for literal text while a
+/// number is formatted.
+const LITERAL_BASE: u32 = 0xE000;
+
+/// Replace the quoted and escaped literal text of a format code with
+/// private-use characters, returning the code and the literals in order.
+fn protect_literals(code: &str) -> (String, Vec<String>) {
+    let mut out = String::with_capacity(code.len());
+    let mut literals = Vec::new();
+    let mut push_literal = |out: &mut String, text: String| {
+        let marker = char::from_u32(LITERAL_BASE + literals.len() as u32).unwrap_or('\u{E000}');
+        literals.push(text);
+        out.push('"');
+        out.push(marker);
+        out.push('"');
+    };
+
+    let mut chars = code.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' => {
+                let text: String = chars.by_ref().take_while(|&q| q != '"').collect();
+                push_literal(&mut out, text);
+            }
+            '\\' => {
+                if let Some(escaped) = chars.next() {
+                    push_literal(&mut out, escaped.to_string());
+                }
+            }
+            '_' | '*' => {
+                out.push(c);
+                out.extend(chars.next());
+            }
+            c => out.push(c),
+        }
+    }
+
+    (out, literals)
+}
+
+/// The literal a private-use character from [`protect_literals`] stands for.
+fn literal_index(c: char) -> Option<usize> {
+    (c as u32).checked_sub(LITERAL_BASE).map(|i| i as usize)
+}
+
+/// Remove `[$-LCID]` locale tags from a format code, returning the locale of
+/// the first recognised tag. Currency tags (`[$€-407]`) keep their symbol as a
+/// quoted literal.
+fn strip_locale_tags(code: &str) -> (Option<&'static Locale>, Cow<'_, str>) {
+    if !code.contains("[$") {
+        return (None, Cow::Borrowed(code));
+    }
+
+    let mut locale = None;
+    let mut out = String::with_capacity(code.len());
+    let mut rest = code;
+
+    while let Some(start) = rest.find("[$") {
+        let Some(len) = rest[start..].find(']') else {
+            break;
+        };
+        out.push_str(&rest[..start]);
+
+        let tag = &rest[start + 2..start + len];
+        let (symbol, lcid) = tag.split_once('-').unwrap_or((tag, ""));
+        if !symbol.is_empty() {
+            out.push('"');
+            out.push_str(symbol);
+            out.push('"');
+        }
+        if locale.is_none() {
+            // The low 16 bits hold the language id; higher bits select
+            // calendars and number systems.
+            locale = u32::from_str_radix(lcid, 16)
+                .ok()
+                .and_then(|id| Locale::from_lcid(id & 0xFFFF));
+        }
+
+        rest = &rest[start + len + 1..];
+    }
+    out.push_str(rest);
+
+    (locale, Cow::Owned(out))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_localize_separators() {
+        let de = Locale::parse("de-DE").unwrap();
+        assert_eq!(de.localize_number("$3,123.45"), "$3.123,45");
+        assert_eq!(de.localize_number("12.5%"), "12,5%");
+        assert_eq!(EN_US.localize_number("3,123.45"), "3,123.45");
+
+        // Literal text in the format code keeps its punctuation
+        let formatted = de.format_number("\"v1.2, \"0.0", |code| {
+            assert!(!code.contains("v1.2"));
+            code.replace("0.0", "3.5")
+        });
+        assert_eq!(formatted, "\"v1.2, \"3,5");
+    }
+
+    #[test]
+    fn test_localize_names() {
+        let date = chrono::NaiveDate::from_ymd_opt(2026, 5, 4).unwrap().and_hms_opt(0, 0, 0).unwrap();
+
+        let fr = Locale::parse("fr").unwrap();
+        assert_eq!(
+            fr.localize_date_code("dddd, mmmm d, yyyy", date).unwrap(),
+            "\"lundi\", \"mai\" d, yyyy"
+        );
+
+        // Short and full names differ, and literals are left alone
+        let es = Locale::parse("es").unwrap();
+        assert_eq!(es.localize_date_code("d mmm", date).unwrap(), "d \"may\"");
+        assert_eq!(es.localize_date_code("d mmmm", date).unwrap(), "d \"mayo\"");
+        assert_eq!(es.localize_date_code("\"Monday\" d mmm", date).unwrap(), "\"Monday\" d \"may\"");
+        assert_eq!(es.localize_date_code("d/m/yyyy", date), None);
+        assert_eq!(EN_US.localize_date_code("d mmm", date), None);
+
+        assert_eq!(es.localize_strftime("%a %d %B %%B", date), "lun %d mayo %%B");
+    }
+
+    #[test]
+    fn test_resolve_format() {
+        let (locale, code) = EN_US.resolve_format(164, "[$-407]dddd, d. mmmm yyyy");
+        assert_eq!(locale.tag, "de-DE");
+        assert_eq!(code, "dddd, d. mmmm yyyy");
+
+        let (_, code) = EN_US.resolve_format(164, "#,##0.00 [$€-407]");
+        assert_eq!(code, "#,##0.00 \"€\"");
+
+        let de = Locale::parse("de-DE").unwrap();
+        assert_eq!(de.resolve_format(14, "m/d/yyyy").1, "dd.mm.yyyy");
+        assert_eq!(EN_US.resolve_format(14, "m/d/yyyy").1, "m/d/yyyy");
+    }
+}
@@ -1,6 +1,15 @@
+mod autofilter;
+mod columns;
 mod error;
 mod evaluator;
+mod filter;
+mod general;
+mod hidden;
+mod hyperlink;
+mod links;
+mod locale;
 mod reader;
+mod schema;
 mod writer;
 
 use clap::{Parser, ValueEnum};
@@ -16,6 +25,20 @@ pub enum OutputFormat {
     European,
 }
 
+/// Date system used to interpret date serial numbers.
+#[derive(Debug, Clone, Copy, ValueEnum, Default, PartialEq)]
+pub enum DateSystem {
+    /// Use the date system recorded in the workbook
+    #[default]
+    Auto,
+    /// Serial 1 is 1900-01-01 (Windows Excel default)
+    #[value(name = "1900")]
+    D1900,
+    /// Serial 0 is 1904-01-01 (older Mac Excel)
+    #[value(name = "1904")]
+    D1904,
+}
+
 impl OutputFormat {
     pub fn delimiter(&self) -> u8 {
         match self {
@@ -37,10 +60,22 @@ pub struct Args {
     #[arg(short, long)]
     pub output: Option<PathBuf>,
 
-    /// Convert specific sheet by name or index (default: all sheets)
+    /// Convert specific sheets (default: all sheets): comma-separated names,
+    /// 0-based indices, index ranges (2..5, 2..=5, 3..), globs (Region_*) or
+    /// regexes (/^FY\d+$/)
     #[arg(short, long)]
     pub sheet: Option<String>,
 
+    /// Match sheet names exactly; by default case and surrounding or repeated
+    /// whitespace are ignored when no sheet has the exact name
+    #[arg(long)]
+    pub strict_sheet_names: bool,
+
+    /// Leave out sheets matching any of these selectors (same syntax as
+    /// --sheet)
+    #[arg(long, value_name = "SHEETS")]
+    pub exclude_sheet: Option<String>,
+
     /// Output format
     #[arg(short, long, value_enum, default_value_t = OutputFormat::Csv)]
     pub format: OutputFormat,
@@ -49,6 +84,129 @@ pub struct Args {
     #[arg(short, long, default_value = "")]
     pub empty: String,
 
+    /// Locale for number and date formatting (e.g. de-DE, fr-FR, ja-JP)
+    #[arg(long, default_value = "en-US")]
+    pub locale: String,
+
+    /// Date system for date serial numbers (default: as recorded in the workbook)
+    #[arg(long, value_enum, default_value_t = DateSystem::Auto)]
+    pub date_system: DateSystem,
+
+    /// Render all date-formatted cells with this strftime format (e.g. %Y-%m-%d)
+    #[arg(long, value_name = "STRFTIME")]
+    pub date_format: Option<String>,
+
+    /// Render all date-and-time-formatted cells with this strftime format
+    /// (e.g. "%Y-%m-%d %H:%M:%S")
+    #[arg(long, value_name = "STRFTIME")]
+    pub datetime_format: Option<String>,
+
+    /// Precision of numeric output: display (as formatted), full (shortest
+    /// exact representation) or a number of significant digits
+    #[arg(long, default_value = "display", value_name = "full|display|N")]
+    pub precision: writer::Precision,
+
+    /// Line breaks inside cells: keep, space, escape (as \n) or a replacement
+    /// string
+    #[arg(long, default_value = "keep", value_name = "keep|space|escape|STRING")]
+    pub newlines: writer::Newlines,
+
+    /// Preserve bold/italic runs of rich text cells as markup
+    #[arg(long, value_enum)]
+    pub rich_text: Option<writer::RichTextMarkup>,
+
+    /// Export hyperlinked cells as their text, the link target, both, or a
+    /// Markdown link
+    #[arg(long, value_enum, default_value_t = hyperlink::HyperlinkMode::Text)]
+    pub hyperlinks: hyperlink::HyperlinkMode,
+
+    /// Export cell comments to a parallel <sheet>.comments.csv (requires -o)
+    /// or inline as an extra column after each commented column. XLSX only;
+    /// notes in XLS files are not exported
+    #[arg(long, value_enum)]
+    pub comments: Option<writer::CommentMode>,
+
+    /// Skip hidden items: rows, cols, sheets (comma-separated); hidden
+    /// sheets include "very hidden" ones
+    #[arg(long, value_enum, value_delimiter = ',', value_name = "KINDS")]
+    pub skip_hidden: Vec<hidden::Hidden>,
+
+    /// Export only hidden items of the given kinds, for auditing
+    #[arg(long, value_enum, value_delimiter = ',', value_name = "KINDS", conflicts_with = "skip_hidden")]
+    pub only_hidden: Vec<hidden::Hidden>,
+
+    /// Re-evaluate each sheet's AutoFilter criteria and export only matching
+    /// rows (XLSX only)
+    #[arg(long)]
+    pub apply_autofilter: bool,
+
+    /// 1-based row holding the column headers; rows above it are dropped
+    #[arg(long, value_name = "N", conflicts_with = "auto_header")]
+    pub header_row: Option<u32>,
+
+    /// Detect the header row, dropping title and notes rows above it
+    #[arg(long)]
+    pub auto_header: bool,
+
+    /// Drop trailing total, notes and blank rows below the data
+    #[arg(long)]
+    pub trim_footer: bool,
+
+    /// Export only these columns, by header name and in this order; rename
+    /// with Old=New (e.g. "Account,Amount=Total,Date")
+    #[arg(long, value_name = "LIST")]
+    pub columns: Option<String>,
+
+    /// Export only rows matching an expression over header-named columns,
+    /// e.g. "Amount >= 1000 AND [Invoice Date] < '2024-07-01'"
+    #[arg(long = "where", value_name = "EXPR")]
+    pub where_expr: Option<String>,
+
+    /// Drop trailing empty rows and columns, including cells that only carry
+    /// formatting
+    #[arg(long)]
+    pub trim: bool,
+
+    /// Drop rows with no values
+    #[arg(long)]
+    pub skip_blank_rows: bool,
+
+    /// Drop columns with no values
+    #[arg(long)]
+    pub skip_blank_cols: bool,
+
+    /// Unpivot into long format, keeping identifier columns and turning the
+    /// others into (variable, value) rows, e.g. id-cols=A:C
+    #[arg(long, value_name = "id-cols=COLUMNS")]
+    pub unpivot: Option<writer::Unpivot>,
+
+    /// Leave out empty values when unpivoting
+    #[arg(long, requires = "unpivot")]
+    pub unpivot_skip_empty: bool,
+
+    /// Export only this range of each sheet (e.g. B3:F40)
+    #[arg(long, value_name = "RANGE")]
+    pub range: Option<String>,
+
+    /// Swap rows and columns; applied before --where, --columns and
+    /// --unpivot, so those see the transposed headers
+    #[arg(long)]
+    pub transpose: bool,
+
+    /// Concatenate all selected sheets into one output (stdout allowed) with
+    /// a leading sheet column, aligning columns by header name
+    #[arg(long)]
+    pub combine: bool,
+
+    /// How --columns and --where names are matched against headers
+    #[arg(long, value_enum, default_value_t = columns::HeaderMatch::Exact)]
+    pub column_match: columns::HeaderMatch,
+
+    /// TOML file mapping columns (by header name or letter) to output types
+    /// and formats
+    #[arg(long, value_name = "PATH")]
+    pub schema: Option<PathBuf>,
+
     /// Print detailed progress to stderr
     #[arg(short, long)]
     pub verbose: bool,
@@ -62,9 +220,30 @@ pub struct Args {
     #[arg(long = "set", value_name = "CELL=VALUE")]
     pub set_cells: Vec<String>,
 
+    /// Override a range before recalculation (format: Sheet1!B3:B40=1,2,3 or
+    /// Sheet1!B3:B40=@values.csv). Inline rows are separated by ';'.
+    /// Can be specified multiple times. Implies --recalculate.
+    #[arg(long = "set-range", value_name = "RANGE=DATA")]
+    pub set_ranges: Vec<String>,
+
+    /// Allow --set-range to replace formula cells with constants
+    #[arg(long)]
+    pub replace_formulas: bool,
+
     /// Override TODAY()/NOW() date for reproducible output (format: YYYY-MM-DD)
     #[arg(long)]
     pub today: Option<String>,
+
+    /// Save the overridden and recalculated workbook as XLSX, keeping
+    /// formulas, styles and number formats. Implies --recalculate.
+    #[arg(long, value_name = "PATH")]
+    pub save_workbook: Option<PathBuf>,
+
+    /// Location of a workbook referenced by external links (format: name=path,
+    /// e.g. rates.xlsx=/data/rates_2026.xlsx). Can be specified multiple times.
+    /// Unmapped links are looked up next to the input file.
+    #[arg(long = "link", value_name = "NAME=PATH")]
+    pub links: Vec<String>,
 }
 
 fn main() {
@@ -82,7 +261,10 @@ fn run(args: Args) -> error::Result<()> {
     }
 
     // Determine if we need to recalculate (explicit flag or --set provided)
-    let needs_recalc = args.recalculate || !args.set_cells.is_empty();
+    let needs_recalc = args.recalculate
+        || !args.set_cells.is_empty()
+        || !args.set_ranges.is_empty()
+        || args.save_workbook.is_some();
 
     // Parse --set arguments into a HashMap
     let overrides: HashMap<String, String> = args
@@ -99,6 +281,32 @@ fn run(args: Args) -> error::Result<()> {
         })
         .collect();
 
+    // Parse --set-range arguments, keeping their order
+    let range_overrides: Vec<(String, String)> = args
+        .set_ranges
+        .iter()
+        .filter_map(|s| match s.split_once('=') {
+            Some((range, data)) => Some((range.to_string(), data.to_string())),
+            None => {
+                eprintln!("warning: ignoring invalid --set-range format: {}", s);
+                None
+            }
+        })
+        .collect();
+
+    // Parse --link arguments into a HashMap
+    let links: HashMap<String, PathBuf> = args
+        .links
+        .iter()
+        .filter_map(|s| match s.split_once('=') {
+            Some((name, path)) => Some((name.to_string(), PathBuf::from(path))),
+            None => {
+                eprintln!("warning: ignoring invalid --link format: {}", s);
+                None
+            }
+        })
+        .collect();
+
     // Parse --today
     let today = args.today.as_ref().and_then(|s| {
         chrono::NaiveDate::parse_from_str(s, "%Y-%m-%d")
@@ -106,6 +314,15 @@ fn run(args: Args) -> error::Result<()> {
             .ok()
     });
 
+    let date1904 = match args.date_system {
+        DateSystem::Auto => reader::detect_date1904(&args.input)?,
+        DateSystem::D1900 => false,
+        DateSystem::D1904 => true,
+    };
+    if args.verbose && date1904 {
+        eprintln!("date system: 1904");
+    }
+
     // Load evaluated workbook if recalculation is needed
     let evaluated = if needs_recalc {
         if args.verbose {
@@ -113,12 +330,25 @@ fn run(args: Args) -> error::Result<()> {
             if !overrides.is_empty() {
                 eprintln!("  overrides: {:?}", overrides);
             }
+            if !range_overrides.is_empty() {
+                eprintln!("  range overrides: {:?}", range_overrides);
+            }
             if let Some(ref t) = today {
                 eprintln!("  today: {}", t);
             }
+            if !links.is_empty() {
+                eprintln!("  links: {:?}", links);
+            }
         }
 
-        let eval_config = evaluator::EvalConfig { overrides, today };
+        let eval_config = evaluator::EvalConfig {
+            overrides,
+            range_overrides,
+            replace_formulas: args.replace_formulas,
+            date1904,
+            today,
+            links,
+        };
         Some(evaluator::EvaluatedWorkbook::load(&args.input, &eval_config)?)
     } else {
         None
@@ -138,24 +368,197 @@ fn run(args: Args) -> error::Result<()> {
         eprintln!("sheets: {}", sheet_names.join(", "));
     }
 
+    let locale = locale::Locale::parse(&args.locale).ok_or_else(|| {
+        error::Error::InvalidArgument(format!(
+            "unknown locale {} (supported: {})",
+            args.locale,
+            locale::Locale::supported()
+        ))
+    })?;
+
+    for strftime in [&args.date_format, &args.datetime_format].into_iter().flatten() {
+        writer::validate_strftime(strftime)?;
+    }
+
+    let schema = args.schema.as_deref().map(schema::Schema::load).transpose()?;
+
+    let columns = args
+        .columns
+        .as_deref()
+        .map(|list| columns::ColumnSelection::parse(list, args.column_match))
+        .transpose()?;
+
+    let filter = args
+        .where_expr
+        .as_deref()
+        .map(|expr| filter::RowFilter::parse(expr, args.column_match))
+        .transpose()?;
+
+    let range = args.range.as_deref().map(parse_range).transpose()?;
+
+    if args.header_row == Some(0) {
+        return Err(error::Error::InvalidArgument(
+            "--header-row is 1-based".to_string(),
+        ));
+    }
+
+    let comments = match args.comments {
+        Some(_) if !is_xlsx(&args.input) => {
+            eprintln!("warning: --comments is only supported for XLSX files; exporting without comments");
+            None
+        }
+        mode => mode,
+    };
+
+    let autofilters = if !args.apply_autofilter {
+        HashMap::new()
+    } else if is_xlsx(&args.input) {
+        autofilter::read_autofilters(&args.input)?
+    } else {
+        eprintln!("warning: --apply-autofilter is only supported for XLSX files; exporting all rows");
+        HashMap::new()
+    };
+    if args.verbose {
+        for (sheet, filter) in &autofilters {
+            eprintln!("autofilter: {} ({} filtered columns)", sheet, filter.columns.len());
+        }
+    }
+
     let config = writer::CsvConfig {
         format: args.format,
         empty_value: args.empty.clone(),
+        locale,
+        date1904,
+        date_format: args.date_format.clone(),
+        datetime_format: args.datetime_format.clone(),
+        schema,
+        precision: args.precision,
+        newlines: args.newlines.clone(),
+        rich_text: args.rich_text,
+        hyperlinks: args.hyperlinks,
+        comments,
+        hidden: if args.only_hidden.is_empty() {
+            hidden::HiddenFilter { kinds: args.skip_hidden.clone(), only_hidden: false }
+        } else {
+            hidden::HiddenFilter { kinds: args.only_hidden.clone(), only_hidden: true }
+        },
+        autofilters,
+        header: match args.header_row {
+            Some(row) => Some(writer::HeaderRow::Row(row)),
+            None if args.auto_header => Some(writer::HeaderRow::Auto),
+            None => None,
+        },
+        trim_footer: args.trim_footer,
+        columns,
+        filter,
+        trim: args.trim,
+        skip_blank_rows: args.skip_blank_rows,
+        skip_blank_cols: args.skip_blank_cols,
+        unpivot: args.unpivot.clone().map(|unpivot| writer::Unpivot {
+            skip_empty: args.unpivot_skip_empty,
+            ..unpivot
+        }),
+        range,
+        transpose: args.transpose,
     };
 
     // Determine which sheets to process
-    let sheets_to_process: Vec<usize> = match &args.sheet {
+    let mut sheets_to_process: Vec<usize> = match &args.sheet {
         Some(selector) => {
-            let sel = reader::parse_sheet_selector(selector);
-            vec![resolve_sheet_index_from_names(&sel, &sheet_names)?]
+            let selectors = reader::parse_sheet_selectors(selector, &sheet_names)?;
+            resolve_sheet_index_from_names(&selectors, &sheet_names, args.strict_sheet_names)?
         }
         None => (0..sheet_names.len()).collect(),
     };
+    if let Some(ref exclude) = args.exclude_sheet {
+        let selectors = reader::parse_sheet_selectors(exclude, &sheet_names)?;
+        let excluded = resolve_sheet_index_from_names(&selectors, &sheet_names, args.strict_sheet_names)?;
+        sheets_to_process.retain(|idx| !excluded.contains(idx));
+        if sheets_to_process.is_empty() {
+            return Err(error::Error::InvalidArgument(
+                "--exclude-sheet leaves no sheets to convert".to_string(),
+            ));
+        }
+    }
+
+    // Look up a sheet as stored in the workbook, for visibility
+    let source_sheet = |idx: usize| match evaluated {
+        Some(ref eval_wb) => eval_wb.source_sheet(&sheet_names[idx]),
+        None => workbook.as_ref().and_then(|wb| wb.get_sheet(&idx)),
+    };
+
+    // Drop hidden sheets (or visible ones, with --only-hidden sheets)
+    let sheets_to_process: Vec<usize> = sheets_to_process
+        .into_iter()
+        .filter(|&idx| {
+            let state = source_sheet(idx).and_then(hidden::sheet_state);
+            let keep = config.hidden.keep(hidden::Hidden::Sheets, state.is_some());
+            if args.verbose && !keep {
+                match state {
+                    Some(state) => eprintln!("skipped {} sheet: {}", state, sheet_names[idx]),
+                    None => eprintln!("skipped visible sheet: {}", sheet_names[idx]),
+                }
+            }
+            keep
+        })
+        .collect();
 
     // Check output constraints
-    if sheets_to_process.len() > 1 && args.output.is_none() {
+    if sheets_to_process.len() > 1 && args.output.is_none() && !args.combine {
         return Err(error::Error::MultipleSheetNoOutput);
     }
+    if config.comments == Some(writer::CommentMode::File) && (args.output.is_none() || args.combine) {
+        return Err(error::Error::InvalidArgument(
+            "--comments file requires -o <file or directory> and no --combine".to_string(),
+        ));
+    }
+
+    // Save only once every argument has been validated
+    if let (Some(save_path), Some(eval_wb)) = (&args.save_workbook, &evaluated) {
+        if args.verbose {
+            eprintln!("saving workbook: {:?}", save_path);
+        }
+        eval_wb.save(save_path)?;
+    }
+
+    // Combine all sheets into a single output
+    if args.combine {
+        let mut sheets = Vec::new();
+        for &sheet_idx in &sheets_to_process {
+            let sheet_name = &sheet_names[sheet_idx];
+            if args.verbose {
+                eprintln!("processing: {}", sheet_name);
+            }
+
+            let rows = if let Some(ref eval_wb) = evaluated {
+                writer::read_evaluated_sheet(eval_wb, sheet_name, &config)?
+            } else {
+                let sheet = source_sheet(sheet_idx).ok_or(error::Error::SheetIndexOutOfRange {
+                    index: sheet_idx,
+                    count: sheet_names.len(),
+                })?;
+                writer::read_sheet(sheet, &config)?
+            };
+            sheets.push((sheet_name.clone(), rows));
+        }
+
+        let combined = writer::combine_sheets(sheets);
+        match &args.output {
+            None => writer::write_grid(&combined, io::stdout().lock(), &config)?,
+            Some(output_path) => {
+                let file_path = if output_path.is_dir() {
+                    output_path.join("combined.csv")
+                } else {
+                    output_path.clone()
+                };
+                if args.verbose {
+                    eprintln!("output: {:?}", file_path);
+                }
+                writer::write_grid(&combined, std::fs::File::create(&file_path)?, &config)?;
+            }
+        }
+        return Ok(());
+    }
 
     // Process sheets
     for &sheet_idx in &sheets_to_process {
@@ -163,6 +566,11 @@ fn run(args: Args) -> error::Result<()> {
 
         if args.verbose {
             eprintln!("processing: {}", sheet_name);
+            if let Some(sheet) = source_sheet(sheet_idx) {
+                for note in config.hidden.describe(&hidden::SheetVisibility::of(sheet)) {
+                    eprintln!("  {}", note);
+                }
+            }
         }
 
         match &args.output {
@@ -200,8 +608,9 @@ fn run(args: Args) -> error::Result<()> {
                 }
 
                 let file = std::fs::File::create(&file_path)?;
-                if let Some(ref eval_wb) = evaluated {
+                let comments = if let Some(ref eval_wb) = evaluated {
                     writer::write_evaluated_sheet(eval_wb, sheet_name, file, &config)?;
+                    eval_wb.source_sheet(sheet_name).map(writer::sheet_comments)
                 } else {
                     let wb = workbook.as_ref().unwrap();
                     let sheet = wb.get_sheet(&sheet_idx).ok_or(error::Error::SheetIndexOutOfRange {
@@ -209,6 +618,21 @@ fn run(args: Args) -> error::Result<()> {
                         count: sheet_names.len(),
                     })?;
                     writer::write_sheet(sheet, file, &config)?;
+                    Some(writer::sheet_comments(sheet))
+                };
+
+                // Comments go next to the sheet's CSV as <sheet>.comments.csv
+                if config.comments == Some(writer::CommentMode::File) {
+                    let comments = comments.unwrap_or_default();
+                    if !comments.is_empty() {
+                        let comments_path =
+                            file_path.with_file_name(format!("{}.comments.csv", sheet_name));
+                        if args.verbose {
+                            eprintln!("comments: {:?}", comments_path);
+                        }
+                        let file = std::fs::File::create(&comments_path)?;
+                        writer::write_comments(&comments, file, &config)?;
+                    }
                 }
             }
         }
@@ -217,9 +641,60 @@ fn run(args: Args) -> error::Result<()> {
     Ok(())
 }
 
+/// Whether a path has an XLSX (or macro-enabled XLSM) extension.
+fn is_xlsx(path: &std::path::Path) -> bool {
+    path.extension()
+        .and_then(|e| e.to_str())
+        .is_some_and(|e| e.eq_ignore_ascii_case("xlsx") || e.eq_ignore_ascii_case("xlsm"))
+}
+
+/// Parse the `--range` option, an A1 range with rows and columns from 1.
+fn parse_range(range: &str) -> error::Result<evaluator::CellRange> {
+    evaluator::parse_a1_range(range)
+        .ok()
+        .filter(|((col, row), _)| *col > 0 && *row > 0)
+        .ok_or_else(|| error::Error::InvalidArgument(format!("invalid --range: {}", range)))
+}
+
+/// Resolve sheet selectors to sheet indices, in the order given and without
+/// duplicates. Every selector must match at least one sheet.
 fn resolve_sheet_index_from_names(
+    selectors: &[reader::SheetSelector],
+    sheet_names: &[String],
+    strict: bool,
+) -> error::Result<Vec<usize>> {
+    let mut indices = Vec::new();
+    for selector in selectors {
+        let matched: Vec<usize> = if selector.is_pattern() {
+            let matched: Vec<usize> = (0..sheet_names.len())
+                .filter(|&idx| selector.matches(idx, &sheet_names[idx]))
+                .collect();
+            if matched.is_empty() {
+                return Err(error::Error::NoSheetMatches {
+                    pattern: selector.to_string(),
+                    available: sheet_names.join(", "),
+                });
+            }
+            matched
+        } else {
+            vec![resolve_single_sheet(selector, sheet_names, strict)?]
+        };
+
+        for idx in matched {
+            if !indices.contains(&idx) {
+                indices.push(idx);
+            }
+        }
+    }
+    Ok(indices)
+}
+
+/// Resolve a single sheet name or index. Unless `strict`, a name without an
+/// exact match matches a sheet that differs only in case and whitespace.
+fn resolve_single_sheet(
     selector: &reader::SheetSelector,
     sheet_names: &[String],
+    strict: bool,
 ) -> error::Result<usize> {
     match selector {
         reader::SheetSelector::Index(idx) => {
@@ -233,13 +708,110 @@ fn resolve_sheet_index_from_names(
             }
         }
         reader::SheetSelector::Name(name) => {
-            sheet_names
-                .iter()
-                .position(|n| n == name)
-                .ok_or_else(|| error::Error::SheetNotFound {
-                    name: name.clone(),
-                    available: sheet_names.join(", "),
-                })
+            if let Some(idx) = sheet_names.iter().position(|n| n == name) {
+                return Ok(idx);
+            }
+
+            if !strict {
+                let key = reader::sheet_name_key(name);
+                let loose: Vec<usize> = (0..sheet_names.len())
+                    .filter(|&idx| reader::sheet_name_key(&sheet_names[idx]) == key)
+                    .collect();
+                match loose[..] {
+                    [idx] => return Ok(idx),
+                    [_, _, ..] => {
+                        return Err(error::Error::AmbiguousSheetName {
+                            name: name.clone(),
+                            candidates: loose
+                                .iter()
+                                .map(|&idx| format!("\"{}\"", sheet_names[idx]))
+                                .collect::<Vec<_>>()
+                                .join(", "),
+                        })
+                    }
+                    [] => {}
+                }
+            }
+
+            Err(error::Error::SheetNotFound {
+                name: name.clone(),
+                suggestion: reader::suggest_sheet_name(name, sheet_names)
+                    .map(|s| format!("; did you mean \"{}\"?", s))
+                    .unwrap_or_default(),
+                available: sheet_names.join(", "),
+            })
+        }
+        _ => unreachable!("patterns are resolved by resolve_sheet_index_from_names"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn names(names: &[&str]) -> Vec<String> {
+        names.iter().map(|n| n.to_string()).collect()
+    }
+
+    fn resolve(list: &str, sheet_names: &[String], strict: bool) -> error::Result<Vec<usize>> {
+        let selectors = reader::parse_sheet_selectors(list, sheet_names)?;
+        resolve_sheet_index_from_names(&selectors, sheet_names, strict)
+    }
+
+    #[test]
+    fn test_parse_range() {
+        assert_eq!(parse_range("B3:F40").unwrap(), ((2, 3), (6, 40)));
+        for invalid in ["A-1:B2", "A.1:B2", "ABCDEFGHIJKLMNOPQRSTUVWXYZ1:B2", "A0:B2", "1:2"] {
+            assert!(
+                matches!(parse_range(invalid), Err(error::Error::InvalidArgument(_))),
+                "accepted {}",
+                invalid
+            );
         }
     }
+
+    #[test]
+    fn test_resolve_sheet_names_loose() {
+        let sheets = names(&["Summary ", "Q1 Data", "Q2 Data", "Notes"]);
+
+        assert_eq!(resolve("summary", &sheets, false).unwrap(), vec![0]);
+        assert_eq!(resolve("q1  data, 3, Q*", &sheets, false).unwrap(), vec![1, 3, 2]);
+        assert!(matches!(
+            resolve("Sumary", &sheets, false),
+            Err(error::Error::SheetNotFound { ref suggestion, .. }) if suggestion.contains("Summary ")
+        ));
+        assert!(matches!(
+            resolve("Z*", &sheets, false),
+            Err(error::Error::NoSheetMatches { .. })
+        ));
+    }
+
+    #[test]
+    fn test_resolve_sheet_names_strict() {
+        let sheets = names(&["Summary ", "Q1 Data"]);
+
+        assert_eq!(resolve("Summary ", &sheets, true).unwrap(), vec![0]);
+        assert!(matches!(
+            resolve("summary", &sheets, true),
+            Err(error::Error::SheetNotFound { .. })
+        ));
+        assert!(matches!(
+            resolve_single_sheet(&reader::SheetSelector::Index(2), &sheets, true),
+            Err(error::Error::SheetIndexOutOfRange { index: 2, count: 2 })
+        ));
+    }
+
+    #[test]
+    fn test_resolve_sheet_names_ambiguous() {
+        let sheets = names(&["Data", "data ", "Other"]);
+
+        let selector = reader::SheetSelector::Name("DATA".to_string());
+        assert!(matches!(
+            resolve_single_sheet(&selector, &sheets, false),
+            Err(error::Error::AmbiguousSheetName { ref candidates, .. })
+                if candidates == "\"Data\", \"data \""
+        ));
+        // An exact name is never ambiguous
+        assert_eq!(resolve("data ", &sheets, false).unwrap(), vec![1]);
+    }
 }
@@ -1,4 +1,6 @@
 use crate::error::{Error, Result};
+use quick_xml::events::Event;
+use std::io::Read;
 use std::path::Path;
 use umya_spreadsheet::Spreadsheet;
 
@@ -34,6 +36,92 @@ fn open_xls(path: &Path) -> Result<Spreadsheet> {
     })
 }
 
+/// Detect whether a workbook uses the 1904 date system (serial 0 is
+/// 1904-01-01 rather than 1900-01-00), as saved by older Mac versions of Excel.
+///
+/// Reads the `date1904` attribute of `<workbookPr>` for XLSX and the DATEMODE
+/// record for XLS.
+pub fn detect_date1904(path: &Path) -> Result<bool> {
+    if !path.exists() {
+        return Err(Error::FileNotFound(path.to_path_buf()));
+    }
+
+    let extension = path
+        .extension()
+        .and_then(|e| e.to_str())
+        .map(|e| e.to_lowercase())
+        .unwrap_or_default();
+
+    let invalid = |details: String| Error::InvalidExcel {
+        path: path.to_path_buf(),
+        details,
+    };
+
+    match extension.as_str() {
+        "xlsx" | "xlsm" => {
+            let file = std::fs::File::open(path)?;
+            let mut archive = zip::ZipArchive::new(file).map_err(|e| invalid(e.to_string()))?;
+            let mut xml = String::new();
+            archive
+                .by_name("xl/workbook.xml")
+                .map_err(|e| invalid(e.to_string()))?
+                .read_to_string(&mut xml)?;
+
+            let mut reader = quick_xml::Reader::from_str(&xml);
+            loop {
+                match reader.read_event() {
+                    Ok(Event::Start(e)) | Ok(Event::Empty(e))
+                        if e.local_name().as_ref() == b"workbookPr" =>
+                    {
+                        let date1904 = e
+                            .attributes()
+                            .flatten()
+                            .find(|a| a.key.local_name().as_ref() == b"date1904")
+                            .is_some_and(|a| matches!(a.value.as_ref(), b"1" | b"true"));
+                        return Ok(date1904);
+                    }
+                    Ok(Event::Eof) => return Ok(false),
+                    Err(e) => return Err(invalid(e.to_string())),
+                    _ => {}
+                }
+            }
+        }
+        "xls" => {
+            let mut compound = cfb::open(path).map_err(|e| invalid(e.to_string()))?;
+            let stream_name = if compound.exists("/Workbook") {
+                "/Workbook"
+            } else {
+                "/Book"
+            };
+            let mut data = Vec::new();
+            compound
+                .open_stream(stream_name)
+                .map_err(|e| invalid(e.to_string()))?
+                .read_to_end(&mut data)?;
+
+            // Walk the BIFF records of the workbook globals substream
+            const DATEMODE: u16 = 0x0022;
+            const EOF: u16 = 0x000A;
+            let mut pos = 0;
+            while pos + 4 <= data.len() {
+                let id = u16::from_le_bytes([data[pos], data[pos + 1]]);
+                let len = u16::from_le_bytes([data[pos + 2], data[pos + 3]]) as usize;
+                let body = &data[(pos + 4).min(data.len())..(pos + 4 + len).min(data.len())];
+                match id {
+                    DATEMODE if body.len() >= 2 => {
+                        return Ok(u16::from_le_bytes([body[0], body[1]]) == 1);
+                    }
+                    EOF => break,
+                    _ => {}
+                }
+                pos += 4 + len;
+            }
+            Ok(false)
+        }
+        _ => Ok(false),
+    }
+}
+
 /// Get list of sheet names from workbook
 pub fn get_sheet_names(workbook: &Spreadsheet) -> Vec<String> {
     let mut names = Vec::new();
@@ -45,17 +133,292 @@ pub fn get_sheet_names(workbook: &Spreadsheet) -> Vec<String> {
     names
 }
 
-/// Parse sheet selector (name or index)
+/// Parse sheet selector (name, index, index range or glob)
 pub fn parse_sheet_selector(selector: &str) -> SheetSelector {
     if let Ok(index) = selector.parse::<usize>() {
-        SheetSelector::Index(index)
+        return SheetSelector::Index(index);
+    }
+
+    if let Some((start, end)) = selector.split_once("..") {
+        let start = start.trim().parse::<usize>().ok();
+        let end = match end.trim() {
+            "" => Some(None),
+            end => match end.strip_prefix('=') {
+                Some(inclusive) => inclusive.trim().parse::<usize>().ok().map(|e| Some(e + 1)),
+                None => end.parse::<usize>().ok().map(Some),
+            },
+        };
+        if let (Some(start), Some(end)) = (start, end) {
+            return SheetSelector::IndexRange { start, end };
+        }
+    }
+
+    if selector.contains(['*', '?']) {
+        SheetSelector::Glob(selector.to_string())
     } else {
         SheetSelector::Name(selector.to_string())
     }
 }
 
+/// Parse a comma-separated list of sheet selectors: names, 0-based indices,
+/// index ranges (`2..5`, `2..=5`, `3..`), globs (`Region_*`) and regexes
+/// (`/^FY\d+$/`, which may contain commas). The whole list, or an item with
+/// or without its surrounding spaces, that is exactly the name of one of
+/// `sheet_names` is taken as that name, so sheets like "Sales, East" or
+/// " Notes" stay selectable.
+pub fn parse_sheet_selectors(list: &str, sheet_names: &[String]) -> Result<Vec<SheetSelector>> {
+    let exact_name = |item: &str| item.parse::<usize>().is_err() && sheet_names.iter().any(|n| n == item);
+
+    if exact_name(list) {
+        return Ok(vec![SheetSelector::Name(list.to_string())]);
+    }
+
+    let mut selectors = Vec::new();
+    let mut rest = list;
+
+    while !rest.is_empty() {
+        let (raw, remainder) = rest.split_once(',').unwrap_or((rest, ""));
+        let item = raw.trim();
+
+        if exact_name(raw) || exact_name(item) {
+            let name = if exact_name(raw) { raw } else { item };
+            selectors.push(SheetSelector::Name(name.to_string()));
+            rest = remainder;
+        } else if let Some(body) = rest.trim_start().strip_prefix('/') {
+            // A regex runs to the next "/" that ends the item
+            let end = body
+                .match_indices('/')
+                .map(|(i, _)| i)
+                .find(|&i| body[i + 1..].trim_start().is_empty() || body[i + 1..].trim_start().starts_with(','))
+                .ok_or_else(|| Error::InvalidArgument(format!("unterminated sheet regex: {}", rest.trim())))?;
+            let pattern = &body[..end];
+            let regex = regex::Regex::new(pattern)
+                .map_err(|e| Error::InvalidArgument(format!("invalid sheet regex /{}/: {}", pattern, e)))?;
+            selectors.push(SheetSelector::Regex(regex));
+            let after = body[end + 1..].trim_start();
+            rest = after.strip_prefix(',').unwrap_or(after);
+        } else {
+            if !item.is_empty() {
+                selectors.push(parse_sheet_selector(item));
+            }
+            rest = remainder;
+        }
+    }
+
+    Ok(selectors)
+}
+
 #[derive(Debug, Clone)]
 pub enum SheetSelector {
     Index(usize),
     Name(String),
+    /// Indices from `start`, up to but excluding `end` (or all remaining).
+    IndexRange { start: usize, end: Option<usize> },
+    /// A name pattern with `*` and `?` wildcards.
+    Glob(String),
+    Regex(regex::Regex),
+}
+
+impl SheetSelector {
+    /// Whether the selector is a pattern that may match any number of sheets.
+    pub fn is_pattern(&self) -> bool {
+        !matches!(self, SheetSelector::Index(_) | SheetSelector::Name(_))
+    }
+
+    /// Whether the sheet at `index` named `name` is selected.
+    pub fn matches(&self, index: usize, name: &str) -> bool {
+        match self {
+            SheetSelector::Index(i) => *i == index,
+            SheetSelector::Name(n) => n == name,
+            SheetSelector::IndexRange { start, end } => {
+                index >= *start && end.is_none_or(|end| index < end)
+            }
+            SheetSelector::Glob(pattern) => glob_match(pattern, name),
+            SheetSelector::Regex(regex) => regex.is_match(name),
+        }
+    }
+}
+
+impl std::fmt::Display for SheetSelector {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SheetSelector::Index(i) => write!(f, "{}", i),
+            SheetSelector::Name(n) | SheetSelector::Glob(n) => f.write_str(n),
+            SheetSelector::IndexRange { start, end: Some(end) } => write!(f, "{}..{}", start, end),
+            SheetSelector::IndexRange { start, end: None } => write!(f, "{}..", start),
+            SheetSelector::Regex(regex) => write!(f, "/{}/", regex),
+        }
+    }
+}
+
+/// A sheet name compared case-insensitively, ignoring leading, trailing and
+/// repeated whitespace.
+pub fn sheet_name_key(name: &str) -> String {
+    name.split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+        .to_lowercase()
+}
+
+/// The sheet name closest to a mistyped one, if any is close enough to be a
+/// plausible typo.
+pub fn suggest_sheet_name<'a>(name: &str, sheet_names: &'a [String]) -> Option<&'a str> {
+    let key = sheet_name_key(name);
+    let max_distance = (key.chars().count() / 3).max(2);
+
+    sheet_names
+        .iter()
+        .map(|n| (crate::columns::edit_distance(&sheet_name_key(n), &key), n))
+        .filter(|&(distance, _)| distance <= max_distance)
+        .min_by_key(|&(distance, _)| distance)
+        .map(|(_, n)| n.as_str())
+}
+
+/// Match a name against a glob pattern with `*` and `?` wildcards.
+fn glob_match(pattern: &str, name: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let name: Vec<char> = name.chars().collect();
+
+    // Iterative matching with backtracking to the last `*`
+    let (mut p, mut n) = (0, 0);
+    let mut star: Option<(usize, usize)> = None;
+    while n < name.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                star = Some((p, n));
+                p += 1;
+            }
+            Some(&c) if c == '?' || c == name[n] => {
+                p += 1;
+                n += 1;
+            }
+            _ => match star {
+                Some((sp, sn)) => {
+                    p = sp + 1;
+                    n = sn + 1;
+                    star = Some((sp, sn + 1));
+                }
+                None => return false,
+            },
+        }
+    }
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_sheet_selectors() {
+        let selectors = parse_sheet_selectors("Q1, 2..5,3..=4, 7.., Region_*, /^FY\\d{2,4}$/ ,Q2", &[]).unwrap();
+        let shown: Vec<String> = selectors.iter().map(|s| s.to_string()).collect();
+        assert_eq!(shown, vec!["Q1", "2..5", "3..5", "7..", "Region_*", "/^FY\\d{2,4}$/", "Q2"]);
+
+        assert!(parse_sheet_selectors("/unterminated", &[]).is_err());
+        assert!(parse_sheet_selectors("/(/", &[]).is_err());
+    }
+
+    #[test]
+    fn test_parse_sheet_selectors_keeps_exact_names() {
+        let names: Vec<String> = ["Sales, East", "/tmp", "1..2", " Notes ", "Summary", "7"]
+            .iter()
+            .map(|n| n.to_string())
+            .collect();
+        let parse = |list: &str| -> Vec<String> {
+            parse_sheet_selectors(list, &names)
+                .unwrap()
+                .iter()
+                .map(|s| format!("{:?}", s))
+                .collect()
+        };
+
+        assert_eq!(parse("Sales, East"), vec!["Name(\"Sales, East\")"]);
+        assert_eq!(parse("/tmp"), vec!["Name(\"/tmp\")"]);
+        assert_eq!(parse("1..2"), vec!["Name(\"1..2\")"]);
+        assert_eq!(parse(" Notes "), vec!["Name(\" Notes \")"]);
+        assert_eq!(parse("Summary, Notes "), vec!["Name(\"Summary\")", "Name(\" Notes \")"]);
+        // Digits stay indices, as before lists were accepted
+        assert_eq!(parse("7"), vec!["Index(7)"]);
+    }
+
+    #[test]
+    fn test_sheet_name_matching() {
+        assert_eq!(sheet_name_key("  Summary  Q1 "), "summary q1");
+
+        let names: Vec<String> = ["Summary ", "Details", "Q1 Data"].iter().map(|n| n.to_string()).collect();
+        assert_eq!(suggest_sheet_name("Sumary", &names), Some("Summary "));
+        assert_eq!(suggest_sheet_name("detail", &names), Some("Details"));
+        assert_eq!(suggest_sheet_name("Revenue", &names), None);
+    }
+
+    #[test]
+    fn test_selector_matches() {
+        let range = SheetSelector::IndexRange { start: 2, end: Some(5) };
+        assert!(range.matches(2, "x") && range.matches(4, "x") && !range.matches(5, "x"));
+        assert!(SheetSelector::Glob("Region_*".to_string()).matches(0, "Region_North"));
+        assert!(!SheetSelector::Glob("Region_?".to_string()).matches(0, "Region_North"));
+        assert!(glob_match("*a*b", "xxaxxb"));
+        assert!(!glob_match("*a*b", "xxaxxbc"));
+    }
+
+    #[test]
+    fn test_detect_date1904_xlsx() {
+        use std::io::Write;
+
+        let write_xlsx = |name: &str, workbook_pr: &str| {
+            let path = std::env::temp_dir().join(format!("excel2csv-{}-{}.xlsx", name, std::process::id()));
+            let mut zip = zip::ZipWriter::new(std::fs::File::create(&path).unwrap());
+            zip.start_file("xl/workbook.xml", zip::write::SimpleFileOptions::default()).unwrap();
+            write!(
+                zip,
+                r#"<workbook xmlns="http://schemas.openxmlformats.org/spreadsheetml/2006/main">{}<sheets/></workbook>"#,
+                workbook_pr
+            )
+            .unwrap();
+            zip.finish().unwrap();
+            path
+        };
+
+        let mac = write_xlsx("date1904", r#"<workbookPr date1904="1" defaultThemeVersion="124226"/>"#);
+        let windows = write_xlsx("date1900", r#"<workbookPr defaultThemeVersion="124226"/>"#);
+        let bare = write_xlsx("no-workbookpr", "");
+        assert!(detect_date1904(&mac).unwrap());
+        assert!(!detect_date1904(&windows).unwrap());
+        assert!(!detect_date1904(&bare).unwrap());
+        for path in [mac, windows, bare] {
+            std::fs::remove_file(path).unwrap();
+        }
+    }
+
+    #[test]
+    fn test_detect_date1904_xls() {
+        use std::io::Write;
+
+        let write_xls = |name: &str, datemode: u16| {
+            let record = |id: u16, body: &[u8]| {
+                let mut bytes = id.to_le_bytes().to_vec();
+                bytes.extend((body.len() as u16).to_le_bytes());
+                bytes.extend(body);
+                bytes
+            };
+            let mut data = record(0x0809, &[0x00, 0x06, 0x05, 0x00, 0, 0, 0, 0]);
+            data.extend(record(0x0022, &datemode.to_le_bytes()));
+            data.extend(record(0x000A, &[]));
+
+            let path = std::env::temp_dir().join(format!("excel2csv-{}-{}.xls", name, std::process::id()));
+            let mut compound = cfb::create(&path).unwrap();
+            compound.create_stream("/Workbook").unwrap().write_all(&data).unwrap();
+            compound.flush().unwrap();
+            path
+        };
+
+        let mac = write_xls("datemode1", 1);
+        let windows = write_xls("datemode0", 0);
+        assert!(detect_date1904(&mac).unwrap());
+        assert!(!detect_date1904(&windows).unwrap());
+        for path in [mac, windows] {
+            std::fs::remove_file(path).unwrap();
+        }
+    }
 }
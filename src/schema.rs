@@ -0,0 +1,418 @@
+//! Per-column output types and formats loaded from a TOML schema file.
+//!
+//! A schema pins how recurring reports are exported, regardless of how the
+//! workbook author formatted each column:
+//!
+//! ```toml
+//! # Row holding the header names used to select columns (default: the
+//! # --header-row or --auto-header row, else 1)
+//! header_row = 1
+//!
+//! [columns]
+//! D = { type = "text" }
+//! "Invoice Date" = { type = "date", format = "%Y-%m-%d" }
+//! Amount = { type = "number", format = "0.00" }
+//! ```
+//!
+//! Columns are selected by header name, or by upper-case column letter when
+//! no header has that name; a key that is neither is an error. Names, letters
+//! and `header_row` refer to the sheet as laid out in the workbook: the schema
+//! is applied before `--range`, hidden rows and columns, and `--transpose`.
+//! Cells that cannot be converted keep their formatted value and are
+//! reported on stderr.
+
+use crate::error::{Error, Result};
+use crate::evaluator::{column_letters, column_number};
+use crate::writer::{format_raw_value, serial_to_datetime, validate_strftime, CellData, CsvConfig, RawValue};
+use chrono::{NaiveDate, NaiveDateTime};
+use serde::Deserialize;
+use ssfmt::FormatOptions;
+use std::collections::BTreeMap;
+use std::path::Path;
+
+/// Number of conversion failures reported individually per sheet.
+const MAX_REPORTED_FAILURES: usize = 10;
+
+/// Column types and formats for export.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Schema {
+    /// 1-based row holding the header names (default: the data's header row).
+    pub header_row: Option<u32>,
+
+    /// Column specs keyed by header name or column letter.
+    #[serde(default)]
+    pub columns: BTreeMap<String, ColumnSpec>,
+}
+
+/// Output type and format of one column.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ColumnSpec {
+    #[serde(rename = "type")]
+    pub kind: ColumnType,
+
+    /// Excel number format code for text/number columns, strftime format for
+    /// date/datetime columns.
+    pub format: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ColumnType {
+    /// Display text; numbers get `format` as an Excel format code if given.
+    Text,
+    /// Unformatted number, or formatted with `format` as an Excel format code.
+    Number,
+    /// Whole number.
+    Integer,
+    /// Date rendered with `format` (default: %Y-%m-%d).
+    Date,
+    /// Date and time rendered with `format` (default: %Y-%m-%d %H:%M:%S).
+    Datetime,
+    /// TRUE or FALSE.
+    Boolean,
+}
+
+impl std::fmt::Display for ColumnType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            ColumnType::Text => "text",
+            ColumnType::Number => "number",
+            ColumnType::Integer => "integer",
+            ColumnType::Date => "date",
+            ColumnType::Datetime => "datetime",
+            ColumnType::Boolean => "boolean",
+        })
+    }
+}
+
+/// Text date layouts accepted for date and datetime columns.
+const DATE_LAYOUTS: [&str; 6] = ["%Y-%m-%d", "%Y/%m/%d", "%m/%d/%Y", "%d.%m.%Y", "%d-%b-%Y", "%d %b %Y"];
+const DATETIME_LAYOUTS: [&str; 4] = [
+    "%Y-%m-%d %H:%M:%S",
+    "%Y-%m-%dT%H:%M:%S",
+    "%Y-%m-%d %H:%M",
+    "%m/%d/%Y %H:%M",
+];
+
+impl Schema {
+    /// Load and validate a schema file.
+    pub fn load(path: &Path) -> Result<Self> {
+        let invalid = |details: String| {
+            Error::InvalidArgument(format!("invalid schema {:?}: {}", path, details))
+        };
+
+        let content = std::fs::read_to_string(path)?;
+        let schema: Schema = toml::from_str(&content).map_err(|e| invalid(e.to_string()))?;
+
+        if schema.header_row == Some(0) {
+            return Err(invalid("header_row is 1-based".to_string()));
+        }
+        for spec in schema.columns.values() {
+            if let (ColumnType::Date | ColumnType::Datetime, Some(format)) = (spec.kind, &spec.format) {
+                validate_strftime(format)?;
+            }
+        }
+
+        Ok(schema)
+    }
+
+    /// Convert the cells of each schema column below the header row, which
+    /// is `data_header` (0-based) unless the schema sets its own.
+    pub fn apply(
+        &self,
+        sheet_name: &str,
+        rows: &mut [Vec<CellData>],
+        data_header: usize,
+        config: &CsvConfig,
+    ) -> Result<()> {
+        for warning in self.convert_rows(sheet_name, rows, data_header, config)? {
+            eprintln!("warning: {}", warning);
+        }
+        Ok(())
+    }
+
+    /// Convert the cells and return warnings about cells that could not be
+    /// converted: at most `MAX_REPORTED_FAILURES` of them, followed by a
+    /// count of the rest.
+    fn convert_rows(
+        &self,
+        sheet_name: &str,
+        rows: &mut [Vec<CellData>],
+        data_header: usize,
+        config: &CsvConfig,
+    ) -> Result<Vec<String>> {
+        let header_idx = self.header_row.map_or(data_header, |row| row as usize - 1);
+        let headers: Vec<String> = rows
+            .get(header_idx)
+            .map(|r| r.iter().map(|c| c.text.trim().to_string()).collect())
+            .unwrap_or_default();
+
+        let targets = self
+            .columns
+            .iter()
+            .map(|(key, spec)| {
+                let col = resolve_column(key, &headers).ok_or_else(|| Error::ColumnNotFound {
+                    name: key.clone(),
+                    sheet: sheet_name.to_string(),
+                    available: headers
+                        .iter()
+                        .filter(|h| !h.is_empty())
+                        .cloned()
+                        .collect::<Vec<_>>()
+                        .join(", "),
+                })?;
+                Ok((col, spec))
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        let mut warnings = Vec::new();
+
+        let opts = FormatOptions::default();
+        let mut failures = 0usize;
+
+        for (row_idx, row) in rows.iter_mut().enumerate().skip(header_idx + 1) {
+            for &(col, spec) in &targets {
+                let Some(cell) = row.get_mut(col) else {
+                    continue;
+                };
+                if cell.raw.is_none() {
+                    continue;
+                }
+
+                match spec.convert(cell, &opts, config) {
+                    Some(text) => cell.text = text,
+                    None => {
+                        failures += 1;
+                        if failures <= MAX_REPORTED_FAILURES {
+                            warnings.push(format!(
+                                "{}!{}{}: cannot convert \"{}\" to {}",
+                                sheet_name,
+                                column_letters(col as u32 + 1),
+                                row_idx + 1,
+                                cell.text,
+                                spec.kind
+                            ));
+                        }
+                    }
+                }
+            }
+        }
+
+        if failures > MAX_REPORTED_FAILURES {
+            warnings.push(format!(
+                "{}: {} more cells could not be converted",
+                sheet_name,
+                failures - MAX_REPORTED_FAILURES
+            ));
+        }
+        Ok(warnings)
+    }
+}
+
+impl ColumnSpec {
+    /// Convert a cell to this column's type, or None if it cannot be converted.
+    fn convert(&self, cell: &CellData, opts: &FormatOptions, config: &CsvConfig) -> Option<String> {
+        let raw = cell.raw.as_ref()?;
+
+        match self.kind {
+            ColumnType::Text => match (raw, &self.format) {
+                (RawValue::Number(_), Some(format)) => {
+                    Some(format_raw_value(raw, 164, format, opts, config))
+                }
+                (RawValue::Text(text), _) => Some(text.clone()),
+                _ => Some(cell.text.clone()),
+            },
+            ColumnType::Number => {
+                let num = as_number(raw)?;
+                Some(match &self.format {
+                    Some(format) => format_raw_value(&RawValue::Number(num), 164, format, opts, config),
+                    None => num.to_string(),
+                })
+            }
+            ColumnType::Integer => {
+                let num = as_number(raw)?;
+                ((num - num.round()).abs() < 1e-9).then(|| format!("{}", num.round() as i64))
+            }
+            ColumnType::Date => {
+                let dt = as_datetime(raw, config.date1904)?;
+                Some(dt.format(self.format.as_deref().unwrap_or("%Y-%m-%d")).to_string())
+            }
+            ColumnType::Datetime => {
+                let dt = as_datetime(raw, config.date1904)?;
+                let format = self.format.as_deref().unwrap_or("%Y-%m-%d %H:%M:%S");
+                Some(dt.format(format).to_string())
+            }
+            ColumnType::Boolean => match raw {
+                RawValue::Number(n) if *n == 0.0 => Some("FALSE".to_string()),
+                RawValue::Number(n) if *n == 1.0 => Some("TRUE".to_string()),
+                RawValue::Text(t) => match t.trim().to_lowercase().as_str() {
+                    "true" | "yes" | "y" | "1" => Some("TRUE".to_string()),
+                    "false" | "no" | "n" | "0" => Some("FALSE".to_string()),
+                    _ => None,
+                },
+                _ => None,
+            },
+        }
+    }
+}
+
+/// Resolve a schema key to a 0-based column index: a matching header name
+/// first, then an upper-case column letter ("D", "AB"). Short header names
+/// like "Qty" that match no header are not taken as column letters.
+fn resolve_column(key: &str, headers: &[String]) -> Option<usize> {
+    let key = key.trim();
+    if let Some(idx) = headers.iter().position(|h| h == key) {
+        return Some(idx);
+    }
+    if !key.chars().all(|c| c.is_ascii_uppercase()) {
+        return None;
+    }
+    column_number(key).map(|col| col as usize - 1)
+}
+
+fn as_number(raw: &RawValue) -> Option<f64> {
+    match raw {
+        RawValue::Number(n) => Some(*n),
+        RawValue::Text(t) => t.trim().parse().ok(),
+    }
+}
+
+fn as_datetime(raw: &RawValue, date1904: bool) -> Option<NaiveDateTime> {
+    match raw {
+        RawValue::Number(n) => serial_to_datetime(*n, date1904),
+        RawValue::Text(t) => {
+            let t = t.trim();
+            DATETIME_LAYOUTS
+                .iter()
+                .find_map(|layout| NaiveDateTime::parse_from_str(t, layout).ok())
+                .or_else(|| {
+                    DATE_LAYOUTS
+                        .iter()
+                        .find_map(|layout| NaiveDate::parse_from_str(t, layout).ok())
+                        .and_then(|d| d.and_hms_opt(0, 0, 0))
+                })
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::writer::grid;
+
+    fn schema(toml: &str) -> Schema {
+        toml::from_str(toml).unwrap()
+    }
+
+    #[test]
+    fn test_parse_schema() {
+        let schema: Schema = toml::from_str(
+            r#"
+            [columns]
+            D = { type = "text" }
+            "Invoice Date" = { type = "date", format = "%d/%m/%Y" }
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(schema.header_row, None);
+        assert!(matches!(schema.columns["D"].kind, ColumnType::Text));
+        assert_eq!(schema.columns["Invoice Date"].format.as_deref(), Some("%d/%m/%Y"));
+    }
+
+    #[test]
+    fn test_resolve_column() {
+        let headers = vec!["ID".to_string(), "Account".to_string()];
+        assert_eq!(resolve_column("Account", &headers), Some(1));
+        assert_eq!(resolve_column("ID", &headers), Some(0));
+        assert_eq!(resolve_column("D", &headers), Some(3));
+        assert_eq!(resolve_column("Missing Header", &headers), None);
+        assert_eq!(resolve_column("Qty", &headers), None);
+        assert_eq!(resolve_column("d", &headers), None);
+    }
+
+    #[test]
+    fn test_apply_uses_data_header_row() {
+        let amount = schema("[columns]\nAmount = { type = \"integer\" }");
+        let mut rows = grid(&[&["Quarterly report", ""], &["Name", "Amount"], &["a", "12.0"]]);
+        amount.apply("Sheet1", &mut rows, 1, &CsvConfig::default()).unwrap();
+        assert_eq!(rows[2][1].text, "12");
+
+        // An explicit header_row takes precedence
+        let explicit = schema("header_row = 1\n[columns]\nAmount = { type = \"integer\" }");
+        let mut rows = grid(&[&["Name", "Amount"], &["a", "7.0"]]);
+        explicit.apply("Sheet1", &mut rows, 1, &CsvConfig::default()).unwrap();
+        assert_eq!(rows[1][1].text, "7");
+    }
+
+    #[test]
+    fn test_convert() {
+        let config = CsvConfig::default();
+        let opts = FormatOptions::default();
+        let convert = |kind: &str, format: Option<&str>, value: &str| {
+            let spec = match format {
+                Some(format) => format!("type = \"{}\"\nformat = \"{}\"", kind, format),
+                None => format!("type = \"{}\"", kind),
+            };
+            let spec: ColumnSpec = toml::from_str(&spec).unwrap();
+            spec.convert(&grid(&[&[value]])[0][0], &opts, &config)
+        };
+
+        assert_eq!(convert("text", None, "ABC-01").as_deref(), Some("ABC-01"));
+        assert_eq!(convert("text", Some("0.00"), "1234.5").as_deref(), Some("1234.50"));
+        assert_eq!(convert("number", None, "3.5").as_deref(), Some("3.5"));
+        assert_eq!(convert("number", Some("0.00"), "3.5").as_deref(), Some("3.50"));
+        assert_eq!(convert("number", None, "n/a"), None);
+        assert_eq!(convert("integer", None, "12.0").as_deref(), Some("12"));
+        assert_eq!(convert("integer", None, "12.5"), None);
+        assert_eq!(convert("date", None, "45366").as_deref(), Some("2024-03-15"));
+        assert_eq!(convert("date", Some("%d/%m/%Y"), "15.03.2024").as_deref(), Some("15/03/2024"));
+        assert_eq!(convert("date", None, "soon"), None);
+        assert_eq!(convert("datetime", None, "45366.5").as_deref(), Some("2024-03-15 12:00:00"));
+        assert_eq!(convert("boolean", None, "1").as_deref(), Some("TRUE"));
+        assert_eq!(convert("boolean", None, "No").as_deref(), Some("FALSE"));
+        assert_eq!(convert("boolean", None, "2"), None);
+    }
+
+    #[test]
+    fn test_convert_rows_reports_failures() {
+        let amount = schema("[columns]\nAmount = { type = \"integer\" }");
+        let mut rows = grid(&[&["Name", "Amount"], &["a", "1.5"], &["b", "2"], &["c", ""]]);
+        let warnings = amount.convert_rows("Sheet1", &mut rows, 0, &CsvConfig::default()).unwrap();
+
+        assert_eq!(warnings, vec!["Sheet1!B2: cannot convert \"1.5\" to integer"]);
+        // Failed cells keep their formatted text
+        assert_eq!(rows[1][1].text, "1.5");
+        assert_eq!(rows[2][1].text, "2");
+    }
+
+    #[test]
+    fn test_convert_rows_caps_reported_failures() {
+        let amount = schema("[columns]\nAmount = { type = \"number\" }");
+        let mut rows = grid(&[&["Amount"]]);
+        rows.extend(grid(&[&["n/a"][..]; MAX_REPORTED_FAILURES + 3]));
+        let warnings = amount.convert_rows("Sheet1", &mut rows, 0, &CsvConfig::default()).unwrap();
+
+        assert_eq!(warnings.len(), MAX_REPORTED_FAILURES + 1);
+        assert_eq!(warnings[0], "Sheet1!A2: cannot convert \"n/a\" to number");
+        assert_eq!(warnings[MAX_REPORTED_FAILURES], "Sheet1: 3 more cells could not be converted");
+    }
+
+    #[test]
+    fn test_missing_column_is_an_error() {
+        let qty = schema("[columns]\nQty = { type = \"integer\" }");
+        let mut rows = grid(&[&["Name", "Amount"], &["a", "2"]]);
+        let err = qty.apply("Sheet1", &mut rows, 0, &CsvConfig::default()).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "column \"Qty\" not found in sheet Sheet1 (available: Name, Amount)"
+        );
+
+        // Upper-case keys still select columns by letter
+        let letter = schema("[columns]\nB = { type = \"integer\" }");
+        letter.apply("Sheet1", &mut rows, 0, &CsvConfig::default()).unwrap();
+        assert_eq!(rows[1][1].text, "2");
+    }
+}
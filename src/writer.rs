@@ -1,14 +1,351 @@
-use crate::error::Result;
-use crate::evaluator::EvaluatedWorkbook;
+use crate::autofilter::AutoFilter;
+use crate::columns::ColumnSelection;
+use crate::error::{Error, Result};
+use crate::filter::RowFilter;
+use crate::evaluator::{column_letters, column_number, CellRange, EvaluatedWorkbook};
+use crate::general;
+use crate::hidden::{HiddenFilter, SheetVisibility};
+use crate::hyperlink::{self, FormulaTarget, HyperlinkMode};
+use crate::locale::Locale;
+use crate::schema::Schema;
 use crate::OutputFormat;
+use clap::ValueEnum;
 use csv::WriterBuilder;
 use ssfmt::{FormatOptions, NumberFormat};
+use std::collections::HashMap;
 use std::io::Write;
-use umya_spreadsheet::structs::Worksheet;
+use umya_spreadsheet::structs::{Cell, RichText, Worksheet};
+
+/// An unformatted cell value, as passed to number formatting.
+#[derive(Debug, Clone, PartialEq)]
+pub enum RawValue {
+    /// Numbers, including dates and times as Excel serial numbers.
+    Number(f64),
+    Text(String),
+}
+
+impl std::fmt::Display for RawValue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RawValue::Number(n) => write!(f, "{}", n),
+            RawValue::Text(s) => f.write_str(s),
+        }
+    }
+}
 
 pub struct CsvConfig {
     pub format: OutputFormat,
     pub empty_value: String,
+    pub locale: &'static Locale,
+    /// Date serial numbers count from 1904-01-01 (Mac 1904 date system).
+    pub date1904: bool,
+    /// strftime format for cells formatted as dates (default: cell format).
+    pub date_format: Option<String>,
+    /// strftime format for cells formatted as dates with times.
+    pub datetime_format: Option<String>,
+    /// Per-column output types and formats.
+    pub schema: Option<Schema>,
+    /// How many digits of numeric cells to output.
+    pub precision: Precision,
+    /// How to write line breaks inside cells.
+    pub newlines: Newlines,
+    /// Markup for bold/italic runs of rich text cells (default: plain text).
+    pub rich_text: Option<RichTextMarkup>,
+    /// How to export cells carrying hyperlinks.
+    pub hyperlinks: HyperlinkMode,
+    /// Where to export cell comments (default: not exported).
+    pub comments: Option<CommentMode>,
+    /// Hidden rows and columns to skip or keep exclusively.
+    pub hidden: HiddenFilter,
+    /// AutoFilters to re-apply, keyed by sheet name.
+    pub autofilters: HashMap<String, AutoFilter>,
+    /// Header row of the data; rows above it are dropped.
+    pub header: Option<HeaderRow>,
+    /// Drop trailing total, notes and blank rows after the data.
+    pub trim_footer: bool,
+    /// Columns to export by header name, in output order.
+    pub columns: Option<ColumnSelection>,
+    /// Expression rows below the header must match to be exported.
+    pub filter: Option<RowFilter>,
+    /// Drop trailing empty rows and columns, including style-only cells.
+    pub trim: bool,
+    /// Drop rows with no values.
+    pub skip_blank_rows: bool,
+    /// Drop columns with no values.
+    pub skip_blank_cols: bool,
+    /// Turn non-identifier columns into (header, value) rows.
+    pub unpivot: Option<Unpivot>,
+    /// Export only this range of each sheet.
+    pub range: Option<CellRange>,
+    /// Swap rows and columns.
+    pub transpose: bool,
+}
+
+/// Long-format output: identifier columns are kept and every other column
+/// becomes a (variable, value) pair on its own row.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Unpivot {
+    /// 0-based identifier columns, counted in the exported columns.
+    pub id_cols: Vec<usize>,
+    /// Leave out pairs whose value is empty.
+    pub skip_empty: bool,
+}
+
+impl std::str::FromStr for Unpivot {
+    type Err = String;
+
+    /// Parse `id-cols=A:C` (ranges and letters, comma-separated).
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        let columns = s
+            .strip_prefix("id-cols=")
+            .ok_or_else(|| format!("expected id-cols=<columns>, got {}", s))?;
+
+        let letter = |l: &str| {
+            column_number(l.trim())
+                .map(|c| c as usize - 1)
+                .ok_or_else(|| format!("invalid column {}", l))
+        };
+        let mut id_cols = Vec::new();
+        for part in columns.split(',').filter(|p| !p.trim().is_empty()) {
+            match part.split_once(':') {
+                Some((first, last)) => {
+                    let (first, last) = (letter(first)?, letter(last)?);
+                    id_cols.extend(first.min(last)..=first.max(last));
+                }
+                None => id_cols.push(letter(part)?),
+            }
+        }
+        id_cols.sort_unstable();
+        id_cols.dedup();
+
+        Ok(Unpivot {
+            id_cols,
+            skip_empty: false,
+        })
+    }
+}
+
+impl Unpivot {
+    /// Melt a grid whose first row is the header.
+    fn apply(&self, rows: Vec<Vec<CellData>>) -> Vec<Vec<CellData>> {
+        let mut rows = rows.into_iter();
+        let Some(header) = rows.next() else {
+            return Vec::new();
+        };
+        let id_cells = |row: &[CellData]| -> Vec<CellData> {
+            self.id_cols
+                .iter()
+                .map(|&col| row.get(col).cloned().unwrap_or_default())
+                .collect()
+        };
+        let value_cols: Vec<usize> = (0..header.len())
+            .filter(|col| !self.id_cols.contains(col))
+            .collect();
+
+        let label = |text: &str| CellData {
+            raw: Some(RawValue::Text(text.to_string())),
+            text: text.to_string(),
+            ..Default::default()
+        };
+        let mut melted = vec![id_cells(&header)];
+        melted[0].extend([label("variable"), label("value")]);
+
+        for row in rows {
+            for &col in &value_cols {
+                let value = row.get(col).cloned().unwrap_or_default();
+                if self.skip_empty && is_blank(&value) {
+                    continue;
+                }
+                let mut out = id_cells(&row);
+                out.push(header[col].clone());
+                out.push(value);
+                melted.push(out);
+            }
+        }
+
+        melted
+    }
+}
+
+/// Where the header row of a sheet's data is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HeaderRow {
+    /// A fixed 1-based row.
+    Row(u32),
+    /// Detected from the sheet's contents.
+    Auto,
+}
+
+/// Rows scanned for a header by `--auto-header`.
+const HEADER_SCAN_ROWS: usize = 50;
+/// Rows below a header candidate checked for consistent types.
+const HEADER_SAMPLE_ROWS: usize = 5;
+
+/// Handling of line breaks (Alt+Enter) inside cell text.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub enum Newlines {
+    /// Write line breaks as-is (the CSV field is quoted).
+    #[default]
+    Keep,
+    /// Replace each line break with a space.
+    Space,
+    /// Replace each line break with a literal `\n`.
+    Escape,
+    /// Replace each line break with the given string.
+    Replace(String),
+}
+
+impl std::str::FromStr for Newlines {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        Ok(match s {
+            "keep" => Newlines::Keep,
+            "space" => Newlines::Space,
+            "escape" => Newlines::Escape,
+            other => Newlines::Replace(other.to_string()),
+        })
+    }
+}
+
+impl Newlines {
+    /// Apply this handling to a cell's text.
+    fn apply(&self, text: &str) -> String {
+        let replacement = match self {
+            Newlines::Keep => return text.to_string(),
+            Newlines::Space => " ",
+            Newlines::Escape => "\\n",
+            Newlines::Replace(s) => s.as_str(),
+        };
+        text.replace("\r\n", "\n")
+            .replace('\r', "\n")
+            .replace('\n', replacement)
+    }
+}
+
+/// Markup used to preserve bold and italic runs of rich text.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum RichTextMarkup {
+    /// `<b>bold</b>` and `<i>italic</i>`, with text HTML-escaped.
+    Html,
+    /// `**bold**` and `*italic*`.
+    Markdown,
+}
+
+/// Output precision for numeric cells that are not dates or times.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Precision {
+    /// As displayed by the cell's number format.
+    #[default]
+    Display,
+    /// Shortest representation that round-trips the stored value.
+    Full,
+    /// A fixed number of significant digits.
+    Significant(usize),
+}
+
+impl std::str::FromStr for Precision {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s {
+            "display" => Ok(Precision::Display),
+            "full" => Ok(Precision::Full),
+            n => match n.parse::<usize>() {
+                Ok(digits) if (1..=17).contains(&digits) => Ok(Precision::Significant(digits)),
+                _ => Err(format!(
+                    "expected full, display or a number of significant digits (1-17), got {}",
+                    s
+                )),
+            },
+        }
+    }
+}
+
+/// A cell prepared for output: its raw value and number format, kept so that
+/// later steps can work with typed values, and its formatted text.
+#[derive(Debug, Clone, Default)]
+pub struct CellData {
+    /// Unformatted value, or None for an empty cell.
+    pub raw: Option<RawValue>,
+    pub format_id: u32,
+    pub format_code: String,
+    /// Formatted display text.
+    pub text: String,
+    /// Hyperlink target, when hyperlinks are exported.
+    pub hyperlink: Option<String>,
+    /// Comment text, when comments are exported inline.
+    pub comment: Option<String>,
+}
+
+/// A cell comment (note) left by a reviewer.
+#[derive(Debug, Clone)]
+pub struct CellComment {
+    pub col: u32,
+    pub row: u32,
+    pub author: String,
+    pub text: String,
+}
+
+/// Where to export cell comments.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum CommentMode {
+    /// A parallel <sheet>.comments.csv listing cell, author and text
+    File,
+    /// An extra column after each column that has comments, headed
+    /// "<header> (comment)"
+    Inline,
+}
+
+/// A grid of cells for tests; numeric-looking text becomes a number.
+#[cfg(test)]
+pub(crate) fn grid(rows: &[&[&str]]) -> Vec<Vec<CellData>> {
+    rows.iter()
+        .map(|r| {
+            r.iter()
+                .map(|t| CellData {
+                    raw: match t.parse::<f64>() {
+                        _ if t.is_empty() => None,
+                        Ok(n) => Some(RawValue::Number(n)),
+                        Err(_) => Some(RawValue::Text(t.to_string())),
+                    },
+                    text: t.to_string(),
+                    ..Default::default()
+                })
+                .collect()
+        })
+        .collect()
+}
+
+impl Default for CsvConfig {
+    fn default() -> Self {
+        CsvConfig {
+            format: OutputFormat::default(),
+            empty_value: String::new(),
+            locale: &crate::locale::EN_US,
+            date1904: false,
+            date_format: None,
+            datetime_format: None,
+            schema: None,
+            precision: Precision::default(),
+            newlines: Newlines::default(),
+            rich_text: None,
+            hyperlinks: HyperlinkMode::default(),
+            comments: None,
+            hidden: HiddenFilter::default(),
+            autofilters: HashMap::new(),
+            header: None,
+            trim_footer: false,
+            columns: None,
+            filter: None,
+            trim: false,
+            skip_blank_rows: false,
+            skip_blank_cols: false,
+            unpivot: None,
+            range: None,
+            transpose: false,
+        }
+    }
 }
 
 pub fn write_sheet<W: Write>(
@@ -16,36 +353,96 @@ pub fn write_sheet<W: Write>(
     writer: W,
     config: &CsvConfig,
 ) -> Result<()> {
-    let mut csv_writer = WriterBuilder::new()
-        .delimiter(config.format.delimiter())
-        .from_writer(writer);
+    write_grid(&read_sheet(sheet, config)?, writer, config)
+}
 
-    let (max_col, max_row) = sheet.get_highest_column_and_row();
-    let opts = FormatOptions::default();
+/// Write an evaluated sheet to CSV output.
+pub fn write_evaluated_sheet<W: Write>(
+    workbook: &EvaluatedWorkbook,
+    sheet_name: &str,
+    writer: W,
+    config: &CsvConfig,
+) -> Result<()> {
+    write_grid(&read_evaluated_sheet(workbook, sheet_name, config)?, writer, config)
+}
 
-    for row in 1..=max_row {
-        let mut record: Vec<String> = Vec::with_capacity(max_col as usize);
+/// Read a worksheet into the rows that would be exported.
+pub fn read_sheet(sheet: &Worksheet, config: &CsvConfig) -> Result<Vec<Vec<CellData>>> {
+    let (max_col, max_row) = if config.trim {
+        data_extent(sheet)
+    } else {
+        sheet.get_highest_column_and_row()
+    };
+    let opts = FormatOptions::default();
 
-        for col in 1..=max_col {
-            let value = get_cell_formatted_value(sheet, col, row, &opts);
-            if value.is_empty() {
-                record.push(config.empty_value.clone());
-            } else {
-                record.push(value);
-            }
-        }
+    let mut rows: Vec<Vec<CellData>> = (1..=max_row)
+        .map(|row| {
+            (1..=max_col)
+                .map(|col| read_worksheet_cell(sheet, col, row, &opts, config))
+                .collect()
+        })
+        .collect();
 
-        csv_writer.write_record(&record)?;
+    if config.comments == Some(CommentMode::Inline) {
+        attach_comments(&mut rows, sheet_comments(sheet));
     }
 
-    csv_writer.flush()?;
-    Ok(())
+    let visibility = SheetVisibility::of(sheet);
+    transform_rows(sheet.get_name(), rows, &visibility, config)
 }
 
-/// Write an evaluated sheet to CSV output.
-pub fn write_evaluated_sheet<W: Write>(
+/// Read an evaluated sheet into the rows that would be exported.
+pub fn read_evaluated_sheet(
     workbook: &EvaluatedWorkbook,
     sheet_name: &str,
+    config: &CsvConfig,
+) -> Result<Vec<Vec<CellData>>> {
+    let (max_col, max_row) = workbook
+        .get_sheet_dimensions(sheet_name)
+        .unwrap_or((0, 0));
+    let opts = FormatOptions::default();
+
+    let mut rows: Vec<Vec<CellData>> = (1..=max_row)
+        .map(|row| {
+            (1..=max_col)
+                .map(|col| read_evaluated_cell(workbook, sheet_name, col, row, &opts, config))
+                .collect()
+        })
+        .collect();
+
+    let source = workbook.source_sheet(sheet_name);
+    if config.comments == Some(CommentMode::Inline) {
+        if let Some(sheet) = source {
+            attach_comments(&mut rows, sheet_comments(sheet));
+        }
+    }
+
+    let visibility = source.map(SheetVisibility::of).unwrap_or_default();
+    transform_rows(sheet_name, rows, &visibility, config)
+}
+
+/// Get the comments of a worksheet, in row then column order.
+pub fn sheet_comments(sheet: &Worksheet) -> Vec<CellComment> {
+    let mut comments: Vec<CellComment> = sheet
+        .get_comments()
+        .iter()
+        .map(|comment| {
+            let coordinate = comment.get_coordinate();
+            CellComment {
+                col: *coordinate.get_col_num(),
+                row: *coordinate.get_row_num(),
+                author: comment.get_author().to_string(),
+                text: comment.get_text().get_text().to_string(),
+            }
+        })
+        .collect();
+    comments.sort_by_key(|c| (c.row, c.col));
+    comments
+}
+
+/// Write comments as CSV with cell reference, author and text columns.
+pub fn write_comments<W: Write>(
+    comments: &[CellComment],
     writer: W,
     config: &CsvConfig,
 ) -> Result<()> {
@@ -53,25 +450,255 @@ pub fn write_evaluated_sheet<W: Write>(
         .delimiter(config.format.delimiter())
         .from_writer(writer);
 
-    let (max_col, max_row) = workbook
-        .get_sheet_dimensions(sheet_name)
-        .unwrap_or((0, 0));
+    csv_writer.write_record(["cell", "author", "text"])?;
+    for comment in comments {
+        let cell = format!("{}{}", column_letters(comment.col), comment.row);
+        csv_writer.write_record([
+            cell.as_str(),
+            comment.author.as_str(),
+            config.newlines.apply(&comment.text).as_str(),
+        ])?;
+    }
+
+    csv_writer.flush()?;
+    Ok(())
+}
+
+/// Attach comments to the cells they annotate, growing the grid for
+/// comments on empty cells beyond the data.
+fn attach_comments(rows: &mut Vec<Vec<CellData>>, comments: Vec<CellComment>) {
+    for comment in comments {
+        let (row, col) = (comment.row as usize - 1, comment.col as usize - 1);
+        if rows.len() <= row {
+            rows.resize_with(row + 1, Vec::new);
+        }
+        let width = rows.iter().map(|r| r.len()).max().unwrap_or(0).max(col + 1);
+        for r in rows.iter_mut() {
+            r.resize_with(width, CellData::default);
+        }
+        rows[row][col].comment = Some(comment.text);
+    }
+}
 
-    for row in 1..=max_row {
-        let mut record: Vec<String> = Vec::with_capacity(max_col as usize);
+/// Apply the configured filters and transformations to a sheet's cells.
+fn transform_rows(
+    sheet_name: &str,
+    mut rows: Vec<Vec<CellData>>,
+    visibility: &SheetVisibility,
+    config: &CsvConfig,
+) -> Result<Vec<Vec<CellData>>> {
+    // Row filters look at sheet positions, so decide them all before
+    // dropping any, and match the autofilter before the schema converts text
+    let mut keep = match config.autofilters.get(sheet_name) {
+        Some(filter) => filter.matching_rows(sheet_name, &rows, config.date1904),
+        None => vec![true; rows.len()],
+    };
 
-        for col in 1..=max_col {
-            let value = workbook
-                .get_value(sheet_name, row, col)
-                .unwrap_or_default();
+    let header = match config.header {
+        Some(HeaderRow::Row(row)) => row as usize - 1,
+        Some(HeaderRow::Auto) => detect_header_row(&rows).unwrap_or_else(|| {
+            eprintln!("warning: {}: no header row detected; keeping all rows", sheet_name);
+            0
+        }),
+        None => 0,
+    };
+    let footer = if config.trim_footer {
+        footer_start(&rows, header)
+    } else {
+        rows.len()
+    };
+    for (i, keep) in keep.iter_mut().enumerate() {
+        *keep &= i >= header && i < footer;
+    }
 
-            if value.is_empty() {
-                record.push(config.empty_value.clone());
-            } else {
-                record.push(value);
+    // The schema names columns and its header row as laid out in the sheet,
+    // so it converts cells before the range, hidden cells and transposing
+    // move them
+    if let Some(ref schema) = config.schema {
+        schema.apply(sheet_name, &mut rows, header, config)?;
+    }
+
+    let width = rows.iter().map(|r| r.len()).max().unwrap_or(0);
+    let mut keep_cols = vec![true; width];
+
+    if let Some(((first_col, first_row), (last_col, last_row))) = config.range {
+        let rows_in_range = first_row as usize - 1..last_row as usize;
+        let cols_in_range = first_col as usize - 1..last_col as usize;
+        for (i, keep) in keep.iter_mut().enumerate() {
+            *keep &= rows_in_range.contains(&i);
+        }
+        for (i, keep) in keep_cols.iter_mut().enumerate() {
+            *keep &= cols_in_range.contains(&i);
+        }
+    }
+    if config.hidden.is_active() {
+        config.hidden.mark_rows(visibility, &mut keep);
+        config.hidden.mark_cols(visibility, &mut keep_cols);
+    }
+
+    let mut keep = keep.into_iter();
+    rows.retain(|_| keep.next().unwrap_or(true));
+    for row in rows.iter_mut() {
+        let mut keep = keep_cols.iter();
+        row.retain(|_| keep.next().copied().unwrap_or(true));
+    }
+
+    // Transposing first lets header-based options see the swapped headers
+    if config.transpose {
+        rows = transpose(rows);
+    }
+
+    if let Some(ref filter) = config.filter {
+        filter.apply(sheet_name, &mut rows, config.date1904)?;
+    }
+    if let Some(ref columns) = config.columns {
+        columns.apply(sheet_name, &mut rows)?;
+    }
+
+    if config.skip_blank_rows {
+        rows.retain(|row| !row.iter().all(is_blank));
+    }
+    if config.skip_blank_cols {
+        drop_blank_cols(&mut rows);
+    }
+    if config.trim {
+        trim_trailing(&mut rows);
+    }
+    if let Some(ref unpivot) = config.unpivot {
+        rows = unpivot.apply(rows);
+    }
+
+    Ok(rows)
+}
+
+/// Concatenate the rows of several sheets under one header, with a leading
+/// sheet-name column. Columns are aligned by header name; sheets whose
+/// headers differ from the first sheet's are reported on stderr.
+pub fn combine_sheets(sheets: Vec<(String, Vec<Vec<CellData>>)>) -> Vec<Vec<CellData>> {
+    // Repeated header names are told apart by their occurrence
+    let keys = |header: &[CellData]| -> Vec<(String, usize)> {
+        let mut seen: HashMap<String, usize> = HashMap::new();
+        header
+            .iter()
+            .map(|cell| {
+                let name = cell.text.trim().to_string();
+                let count = seen.entry(name.clone()).or_default();
+                *count += 1;
+                (name, *count)
+            })
+            .collect()
+    };
+
+    let mut columns: Vec<(String, usize)> = Vec::new();
+    let mut first: Option<(String, Vec<(String, usize)>)> = None;
+    for (name, rows) in &sheets {
+        let sheet_keys = keys(rows.first().map(Vec::as_slice).unwrap_or_default());
+        for key in &sheet_keys {
+            if !columns.contains(key) {
+                columns.push(key.clone());
+            }
+        }
+
+        match first {
+            None => first = Some((name.clone(), sheet_keys)),
+            Some((ref first_name, ref first_keys)) => {
+                let describe = |keys: Vec<&(String, usize)>| {
+                    keys.iter().map(|(h, _)| format!("\"{}\"", h)).collect::<Vec<_>>().join(", ")
+                };
+                let missing: Vec<_> = first_keys.iter().filter(|k| !sheet_keys.contains(k)).collect();
+                let extra: Vec<_> = sheet_keys.iter().filter(|k| !first_keys.contains(k)).collect();
+                if !missing.is_empty() {
+                    eprintln!(
+                        "warning: sheet {} lacks columns of {}: {}",
+                        name,
+                        first_name,
+                        describe(missing)
+                    );
+                }
+                if !extra.is_empty() {
+                    eprintln!(
+                        "warning: sheet {} has columns not in {}: {}",
+                        name,
+                        first_name,
+                        describe(extra)
+                    );
+                }
             }
         }
+    }
+
+    let text = |text: &str| CellData {
+        raw: Some(RawValue::Text(text.to_string())),
+        text: text.to_string(),
+        ..Default::default()
+    };
+
+    let mut header = vec![text("sheet")];
+    header.extend(columns.iter().map(|(name, _)| text(name)));
+    let mut combined = vec![header];
+
+    for (name, rows) in sheets {
+        let mut rows = rows.into_iter();
+        let Some(sheet_header) = rows.next() else {
+            continue;
+        };
+        let sheet_keys = keys(&sheet_header);
+        let positions: Vec<Option<usize>> = columns
+            .iter()
+            .map(|key| sheet_keys.iter().position(|k| k == key))
+            .collect();
+
+        for row in rows {
+            let mut out = vec![text(&name)];
+            out.extend(
+                positions
+                    .iter()
+                    .map(|pos| pos.and_then(|p| row.get(p).cloned()).unwrap_or_default()),
+            );
+            combined.push(out);
+        }
+    }
 
+    combined
+}
+
+/// Write exported rows as CSV, rendering hyperlinks, inline comments, line
+/// breaks and empty values.
+pub fn write_grid<W: Write>(rows: &[Vec<CellData>], writer: W, config: &CsvConfig) -> Result<()> {
+    let mut csv_writer = WriterBuilder::new()
+        .delimiter(config.format.delimiter())
+        .from_writer(writer);
+
+    // Inline comments get a column after each column that has any; in the
+    // header row it is named after the column
+    let width = rows.iter().map(|r| r.len()).max().unwrap_or(0);
+    let commented: Vec<bool> = (0..width)
+        .map(|col| rows.iter().any(|r| r.get(col).is_some_and(|c| c.comment.is_some())))
+        .collect();
+
+    let output = |text: &str| {
+        if text.is_empty() {
+            config.empty_value.clone()
+        } else {
+            config.newlines.apply(text)
+        }
+    };
+
+    for (i, row) in rows.iter().enumerate() {
+        let mut record: Vec<String> = Vec::with_capacity(row.len());
+        for (col, cell) in row.iter().enumerate() {
+            let text = match cell.hyperlink {
+                Some(ref url) => config.hyperlinks.render(&cell.text, url),
+                None => cell.text.clone(),
+            };
+            record.push(output(&text));
+            if commented[col] {
+                record.push(match i {
+                    0 => comment_header(&cell.text, col),
+                    _ => output(cell.comment.as_deref().unwrap_or_default()),
+                });
+            }
+        }
         csv_writer.write_record(&record)?;
     }
 
@@ -79,36 +706,838 @@ pub fn write_evaluated_sheet<W: Write>(
     Ok(())
 }
 
-/// Get a cell's formatted value using ssfmt for proper Excel format code support.
-fn get_cell_formatted_value(sheet: &Worksheet, col: u32, row: u32, opts: &FormatOptions) -> String {
+/// Header of the inline comment column after column `col` (0-based): the
+/// column's header followed by "(comment)", or its letter when the header
+/// is blank.
+fn comment_header(header: &str, col: usize) -> String {
+    match header.trim() {
+        "" => format!("{} (comment)", column_letters(col as u32 + 1)),
+        header => format!("{} (comment)", header),
+    }
+}
+
+/// The extent of a worksheet's values as (max_col, max_row), ignoring cells
+/// that only carry formatting.
+fn data_extent(sheet: &Worksheet) -> (u32, u32) {
+    sheet
+        .get_cell_collection()
+        .iter()
+        .filter(|cell| cell.is_formula() || !cell.get_value().trim().is_empty())
+        .fold((0, 0), |(max_col, max_row), cell| {
+            let coordinate = cell.get_coordinate();
+            (
+                max_col.max(*coordinate.get_col_num()),
+                max_row.max(*coordinate.get_row_num()),
+            )
+        })
+}
+
+/// Swap the rows and columns of a grid, padding short rows with empty cells.
+fn transpose(rows: Vec<Vec<CellData>>) -> Vec<Vec<CellData>> {
+    let width = rows.iter().map(|r| r.len()).max().unwrap_or(0);
+    let mut columns: Vec<Vec<CellData>> = (0..width).map(|_| Vec::with_capacity(rows.len())).collect();
+
+    for row in rows {
+        let len = row.len();
+        for (col, cell) in row.into_iter().enumerate() {
+            columns[col].push(cell);
+        }
+        for column in &mut columns[len..] {
+            column.push(CellData::default());
+        }
+    }
+
+    columns
+}
+
+/// Whether a cell has nothing to export.
+fn is_blank(cell: &CellData) -> bool {
+    cell.text.trim().is_empty() && cell.comment.is_none()
+}
+
+/// Drop trailing blank rows, then trailing columns that are blank in every row.
+fn trim_trailing(rows: &mut Vec<Vec<CellData>>) {
+    while rows.last().is_some_and(|row| row.iter().all(is_blank)) {
+        rows.pop();
+    }
+
+    let width = rows
+        .iter()
+        .filter_map(|row| row.iter().rposition(|c| !is_blank(c)))
+        .max()
+        .map_or(0, |last| last + 1);
+    for row in rows.iter_mut() {
+        row.truncate(width);
+    }
+}
+
+/// Drop columns that are blank in every row.
+fn drop_blank_cols(rows: &mut [Vec<CellData>]) {
+    let width = rows.iter().map(|r| r.len()).max().unwrap_or(0);
+    let filled: Vec<bool> = (0..width)
+        .map(|col| rows.iter().any(|r| r.get(col).is_some_and(|c| !is_blank(c))))
+        .collect();
+
+    for row in rows.iter_mut() {
+        let mut col = 0;
+        row.retain(|_| {
+            col += 1;
+            filled[col - 1]
+        });
+    }
+}
+
+/// Find the header row (0-based): the first dense, mostly-text row whose
+/// following rows are filled in with consistent types.
+fn detect_header_row(rows: &[Vec<CellData>]) -> Option<usize> {
+    let filled = |row: &[CellData]| row.iter().filter(|c| !c.text.trim().is_empty()).count();
+    let scan = &rows[..rows.len().min(HEADER_SCAN_ROWS)];
+    let widest = scan.iter().map(|r| filled(r)).max().unwrap_or(0);
+
+    (0..scan.len()).find(|&i| {
+        let row = &rows[i];
+        let count = filled(row);
+        if count < 2 || count * 5 < widest * 3 {
+            return false;
+        }
+
+        // Mostly text, and no repeated names
+        let texts: Vec<&str> = row
+            .iter()
+            .filter(|c| matches!(c.raw, Some(RawValue::Text(_))))
+            .map(|c| c.text.trim())
+            .filter(|t| !t.is_empty())
+            .collect();
+        let unique: std::collections::HashSet<&str> = texts.iter().copied().collect();
+        if texts.len() * 5 < count * 4 || unique.len() < texts.len() {
+            return false;
+        }
+
+        // Most rows below fill most of the header's columns, each column
+        // with one type of value
+        let sample: Vec<&Vec<CellData>> = rows[i + 1..]
+            .iter()
+            .filter(|r| filled(r) > 0)
+            .take(HEADER_SAMPLE_ROWS)
+            .collect();
+        let dense: Vec<&Vec<CellData>> =
+            sample.iter().copied().filter(|r| filled(r) * 2 >= count).collect();
+        if dense.len() < 2 || dense.len() * 2 <= sample.len() {
+            return false;
+        }
+
+        let columns: Vec<usize> = (0..row.len())
+            .filter(|&col| !row[col].text.trim().is_empty())
+            .collect();
+        let consistent = columns
+            .iter()
+            .filter(|&&col| {
+                let kinds: Vec<u8> = dense
+                    .iter()
+                    .filter_map(|r| r.get(col))
+                    .filter(|c| !c.text.trim().is_empty())
+                    .map(|c| matches!(c.raw, Some(RawValue::Number(_))) as u8)
+                    .collect();
+                kinds.windows(2).all(|w| w[0] == w[1])
+            })
+            .count();
+        consistent * 5 >= columns.len() * 4
+    })
+}
+
+/// Find where the footer starts (0-based): trailing blank rows, total rows
+/// and single-cell notes below the data.
+fn footer_start(rows: &[Vec<CellData>], header: usize) -> usize {
+    const TOTAL_LABELS: [&str; 5] = ["total", "subtotal", "sub-total", "grand total", "sum"];
+
+    // Single-cell rows are only notes when the data has several columns
+    let wide = rows
+        .get(header)
+        .is_some_and(|r| r.iter().filter(|c| !c.text.trim().is_empty()).count() > 1);
+
+    let is_footer = |row: &[CellData]| {
+        let mut filled = row.iter().filter(|c| !c.text.trim().is_empty());
+        let Some(first) = filled.next() else {
+            return true;
+        };
+        let label = first.text.trim().trim_end_matches(':').to_lowercase();
+        let is_total = matches!(first.raw, Some(RawValue::Text(_)))
+            && TOTAL_LABELS
+                .iter()
+                .any(|t| label == *t || label.starts_with(&format!("{} ", t)));
+        let is_note =
+            wide && filled.next().is_none() && matches!(first.raw, Some(RawValue::Text(_)));
+        is_total || is_note
+    };
+
+    let mut end = rows.len();
+    while end > header + 1 && is_footer(&rows[end - 1]) {
+        end -= 1;
+    }
+    end
+}
+
+/// Read a worksheet cell, formatting it using ssfmt for proper Excel format
+/// code support.
+fn read_worksheet_cell(
+    sheet: &Worksheet,
+    col: u32,
+    row: u32,
+    opts: &FormatOptions,
+    config: &CsvConfig,
+) -> CellData {
     let Some(cell) = sheet.get_cell((col, row)) else {
-        return String::new();
+        return CellData::default();
     };
 
     let raw_value = cell.get_value();
     if raw_value.is_empty() {
-        return String::new();
+        return CellData::default();
+    }
+
+    let (format_id, format_code) = cell_number_format(cell);
+    let hyperlink = if config.hyperlinks != HyperlinkMode::Text {
+        cell_hyperlink(cell, |col, row| Some(sheet.get_value((col, row))))
+    } else {
+        None
+    };
+
+    // Rich text is stored as runs; join them rather than relying on the raw value
+    if let Some(rich_text) = cell.get_cell_value().get_rich_text() {
+        return CellData {
+            raw: Some(RawValue::Text(rich_text_plain(rich_text))),
+            format_id,
+            format_code,
+            text: match config.rich_text {
+                Some(markup) => rich_text_markup(rich_text, markup),
+                None => rich_text_plain(rich_text),
+            },
+            hyperlink,
+            comment: None,
+        };
+    }
+
+    // Try to parse as number (includes dates which are serial numbers in Excel)
+    let value = match raw_value.parse::<f64>() {
+        Ok(num) => RawValue::Number(num),
+        Err(_) => RawValue::Text(raw_value.to_string()),
+    };
+
+    let text = format_cell_value(&value, format_id, &format_code, opts, config);
+    CellData {
+        raw: Some(value),
+        format_id,
+        format_code,
+        text,
+        hyperlink,
+        comment: None,
+    }
+}
+
+/// Read a recalculated cell, formatted with its original number format.
+fn read_evaluated_cell(
+    workbook: &EvaluatedWorkbook,
+    sheet_name: &str,
+    col: u32,
+    row: u32,
+    opts: &FormatOptions,
+    config: &CsvConfig,
+) -> CellData {
+    let Some(value) = workbook.get_raw_value(sheet_name, row, col) else {
+        return CellData::default();
+    };
+
+    let (format_id, format_code) = workbook.get_number_format(sheet_name, row, col);
+
+    // Overridden cells no longer carry the original text or formula
+    let source = workbook
+        .source_cell(sheet_name, row, col)
+        .filter(|_| !workbook.is_overridden(sheet_name, row, col));
+
+    let hyperlink = match source {
+        Some(cell) if config.hyperlinks != HyperlinkMode::Text => cell_hyperlink(cell, |c, r| {
+            workbook.get_raw_value(sheet_name, r, c).map(|v| v.to_string())
+        }),
+        _ => None,
+    };
+
+    // Constant rich text cells keep their runs' markup
+    if let Some(markup) = config.rich_text {
+        let rich_text = source
+            .filter(|c| !c.is_formula())
+            .and_then(|c| c.get_cell_value().get_rich_text());
+        if let Some(rich_text) = rich_text {
+            return CellData {
+                raw: Some(value),
+                format_id,
+                format_code,
+                text: rich_text_markup(rich_text, markup),
+                hyperlink,
+                comment: None,
+            };
+        }
+    }
+
+    let text = format_cell_value(&value, format_id, &format_code, opts, config);
+    CellData {
+        raw: Some(value),
+        format_id,
+        format_code,
+        text,
+        hyperlink,
+        comment: None,
+    }
+}
+
+/// Get the hyperlink target of a cell, from the worksheet's hyperlinks or a
+/// `HYPERLINK()` formula. `cell_value` looks up the text of a cell on the same
+/// sheet, by (col, row), for formulas that take their URL from another cell.
+fn cell_hyperlink<F>(cell: &Cell, cell_value: F) -> Option<String>
+where
+    F: Fn(u32, u32) -> Option<String>,
+{
+    if let Some(link) = cell.get_hyperlink() {
+        let url = link.get_url();
+        // Links to places in the workbook are stored without the leading '#'
+        return Some(if *link.get_location() {
+            format!("#{}", url)
+        } else {
+            url.to_string()
+        });
+    }
+
+    if !cell.is_formula() {
+        return None;
+    }
+
+    match hyperlink::formula_target(cell.get_formula())? {
+        FormulaTarget::Literal(url) => Some(url),
+        FormulaTarget::Cell(col, row) => cell_value(col, row).filter(|url| !url.is_empty()),
     }
+}
+
+/// Concatenate the runs of a rich text value.
+fn rich_text_plain(rich_text: &RichText) -> String {
+    rich_text
+        .get_rich_text_elements()
+        .iter()
+        .map(|element| element.get_text())
+        .collect()
+}
 
-    // Get the format code
-    let format_code = cell
-        .get_style()
+/// Concatenate the runs of a rich text value, marking up bold and italic runs.
+fn rich_text_markup(rich_text: &RichText, markup: RichTextMarkup) -> String {
+    let mut out = String::new();
+
+    for element in rich_text.get_rich_text_elements() {
+        let text = element.get_text();
+        let (bold, italic) = element
+            .get_run_properties()
+            .map(|font| (*font.get_bold(), *font.get_italic()))
+            .unwrap_or((false, false));
+
+        match markup {
+            RichTextMarkup::Html => {
+                let escaped = text
+                    .replace('&', "&amp;")
+                    .replace('<', "&lt;")
+                    .replace('>', "&gt;");
+                let (open, close) = match (bold, italic) {
+                    (true, true) => ("<b><i>", "</i></b>"),
+                    (true, false) => ("<b>", "</b>"),
+                    (false, true) => ("<i>", "</i>"),
+                    (false, false) => ("", ""),
+                };
+                out.push_str(open);
+                out.push_str(&escaped);
+                out.push_str(close);
+            }
+            RichTextMarkup::Markdown => {
+                let marker = match (bold, italic) {
+                    (true, true) => "***",
+                    (true, false) => "**",
+                    (false, true) => "*",
+                    (false, false) => "",
+                };
+                // Emphasis markers must hug the text, so keep surrounding
+                // whitespace outside them
+                let trimmed = text.trim();
+                if marker.is_empty() || trimmed.is_empty() {
+                    out.push_str(text);
+                    continue;
+                }
+                let leading = &text[..text.len() - text.trim_start().len()];
+                let trailing = &text[text.trim_end().len()..];
+                out.push_str(leading);
+                out.push_str(marker);
+                out.push_str(trimmed);
+                out.push_str(marker);
+                out.push_str(trailing);
+            }
+        }
+    }
+
+    out
+}
+
+/// Get a cell's number format as (built-in format ID, format code).
+/// Cells without a number format are "General" (ID 0).
+pub(crate) fn cell_number_format(cell: &Cell) -> (u32, String) {
+    cell.get_style()
         .get_number_format()
-        .map(|nf| nf.get_format_code())
-        .unwrap_or("General");
+        .map(|nf| (*nf.get_number_format_id(), nf.get_format_code().to_string()))
+        .unwrap_or_else(|| (0, "General".to_string()))
+}
+
+/// Format a cell value for output: numbers (other than dates and times) at the
+/// configured precision, everything else with its number format.
+fn format_cell_value(
+    value: &RawValue,
+    format_id: u32,
+    format_code: &str,
+    opts: &FormatOptions,
+    config: &CsvConfig,
+) -> String {
+    if let RawValue::Number(num) = value {
+        let digits = match config.precision {
+            Precision::Display => None,
+            Precision::Full => Some(format_full(*num)),
+            Precision::Significant(n) => Some(format_significant(*num, n)),
+        };
+        if let Some(digits) = digits {
+            let (locale, format_code) = config.locale.resolve_format(format_id, format_code);
+            if !is_date_format(&format_code) {
+                return locale.localize_number(&digits);
+            }
+        }
+    }
 
-    // Parse the format code
-    let fmt = match NumberFormat::parse(format_code) {
-        Ok(f) => f,
-        Err(_) => return raw_value.to_string(),
+    format_raw_value(value, format_id, format_code, opts, config)
+}
+
+/// Shortest decimal representation that parses back to the same f64, using
+/// scientific notation for very large and very small magnitudes.
+fn format_full(value: f64) -> String {
+    let magnitude = value.abs();
+    if magnitude != 0.0 && !(1e-5..1e16).contains(&magnitude) {
+        format!("{:e}", value)
+    } else {
+        format!("{}", value)
+    }
+}
+
+/// Format a number with exactly `digits` significant digits, keeping
+/// trailing zeros (e.g. 2 to 3 digits is "2.00", 12345 is "12300").
+fn format_significant(value: f64, digits: usize) -> String {
+    let digits = digits.max(1);
+    if value == 0.0 {
+        return format!("{:.*}", digits - 1, 0.0);
+    }
+
+    // Exponent after rounding to the requested digits (9.99 -> 1.00e1)
+    let scientific = format!("{:.*e}", digits - 1, value);
+    let exponent: i32 = scientific
+        .rsplit_once('e')
+        .and_then(|(_, e)| e.parse().ok())
+        .unwrap_or(0);
+
+    let decimals = digits as i32 - 1 - exponent;
+    if decimals >= 0 {
+        format!("{:.*}", decimals as usize, value)
+    } else {
+        let scale = 10f64.powi(-decimals);
+        format!("{:.0}", (value / scale).round() * scale)
+    }
+}
+
+/// Apply an Excel number format code to a raw value using ssfmt, localized
+/// for the configured locale (or the locale named by a `[$-407]` tag in the
+/// code). General, and format codes ssfmt cannot parse, use the in-crate
+/// General implementation.
+pub(crate) fn format_raw_value(
+    value: &RawValue,
+    format_id: u32,
+    format_code: &str,
+    opts: &FormatOptions,
+    config: &CsvConfig,
+) -> String {
+    let (locale, format_code) = config.locale.resolve_format(format_id, format_code);
+
+    // ssfmt mishandles General, and formats it cannot parse fall back to General
+    let fmt = match NumberFormat::parse(&format_code) {
+        Ok(f) if !general::is_general(&format_code) => f,
+        _ => {
+            return match value {
+                RawValue::Number(num) => locale.localize_number(&general::format_general(*num)),
+                RawValue::Text(text) => text.clone(),
+            };
+        }
     };
 
-    // Try to parse as number (includes dates which are serial numbers in Excel)
-    if let Ok(num) = raw_value.parse::<f64>() {
-        return fmt.format(num, opts);
+    match value {
+        RawValue::Number(num) => {
+            let date_kind = classify_date_format(&format_code);
+            let uniform_format = match date_kind {
+                Some(DateKind::Date) => config.date_format.as_deref(),
+                Some(DateKind::DateTime) => config.datetime_format.as_deref(),
+                _ => None,
+            };
+            if let Some(strftime) = uniform_format {
+                if let Some(dt) = serial_to_datetime(*num, config.date1904) {
+                    return dt.format(&locale.localize_strftime(strftime, dt)).to_string();
+                }
+            }
+
+            // ssfmt counts from 1900; shift 1904-based dates onto that epoch.
+            // Times of day and elapsed times do not depend on the epoch.
+            let on_calendar = matches!(date_kind, Some(DateKind::Date | DateKind::DateTime))
+                && !is_elapsed_format(&format_code);
+            let num = if on_calendar && config.date1904 {
+                *num + DATE1904_OFFSET
+            } else {
+                *num
+            };
+            if date_kind.is_none() {
+                return locale.format_number(&format_code, |code| match NumberFormat::parse(code) {
+                    Ok(localized) => localized.format(num, opts),
+                    Err(_) => fmt.format(num, opts),
+                });
+            }
+
+            // Month and day names are substituted into the code as literals
+            let localized = serial_to_datetime(num, false)
+                .and_then(|dt| locale.localize_date_code(&format_code, dt))
+                .and_then(|code| NumberFormat::parse(&code).ok());
+            localized.as_ref().unwrap_or(&fmt).format(num, opts)
+        }
+        RawValue::Text(text) => fmt.format_text(text, opts),
+    }
+}
+
+/// Days between the 1900 and 1904 date system epochs.
+const DATE1904_OFFSET: f64 = 1462.0;
+
+/// What a date/time number format displays.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum DateKind {
+    /// A calendar date without a time of day.
+    Date,
+    /// A calendar date with a time of day.
+    DateTime,
+    /// A time of day or elapsed time without a date.
+    Time,
+}
+
+/// Whether a format code displays dates or times.
+pub(crate) fn is_date_format(format_code: &str) -> bool {
+    classify_date_format(format_code).is_some()
+}
+
+/// Classify a format code by its date/time placeholders (d, m, y, h, s,
+/// AM/PM and elapsed-time markers like `[h]`), ignoring quoted text, escapes
+/// and other bracketed sections. `m` counts as a month unless the format has
+/// time placeholders and no other date placeholders (e.g. `mm:ss`).
+pub(crate) fn classify_date_format(format_code: &str) -> Option<DateKind> {
+    if format_code.eq_ignore_ascii_case("General") {
+        return None;
+    }
+
+    let (mut day_or_year, mut month, mut time) = (false, false, false);
+
+    let chars: Vec<char> = format_code.chars().collect();
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        match c {
+            '"' => {
+                i += 1;
+                while i < chars.len() && chars[i] != '"' {
+                    i += 1;
+                }
+            }
+            '\\' | '_' | '*' => {
+                i += 1;
+            }
+            '[' => {
+                let mut inner = String::new();
+                i += 1;
+                while i < chars.len() && chars[i] != ']' {
+                    inner.push(chars[i].to_ascii_lowercase());
+                    i += 1;
+                }
+                if !inner.is_empty() && inner.chars().all(|c| matches!(c, 'h' | 'm' | 's')) {
+                    time = true;
+                }
+            }
+            'a' | 'A' => {
+                let rest: String = chars[i..].iter().take(5).collect::<String>().to_uppercase();
+                if rest.starts_with("AM/PM") {
+                    time = true;
+                    i += 4;
+                } else if rest.starts_with("A/P") {
+                    time = true;
+                    i += 2;
+                }
+            }
+            'd' | 'D' | 'y' | 'Y' => day_or_year = true,
+            'm' | 'M' => month = true,
+            'h' | 'H' | 's' | 'S' => time = true,
+            _ => {}
+        }
+        i += 1;
+    }
+
+    let date = day_or_year || (month && !time);
+    match (date, time) {
+        (true, true) => Some(DateKind::DateTime),
+        (true, false) => Some(DateKind::Date),
+        (false, true) => Some(DateKind::Time),
+        (false, false) => None,
+    }
+}
+
+/// Whether a format code shows elapsed time (`[h]`, `[mm]`, `[ss]`), which
+/// counts from zero rather than from the date system's epoch.
+fn is_elapsed_format(format_code: &str) -> bool {
+    // Quoted text is in the odd-numbered pieces between quotes
+    format_code
+        .split('"')
+        .step_by(2)
+        .flat_map(|part| part.split('[').skip(1))
+        .filter_map(|section| section.split_once(']'))
+        .any(|(inner, _)| {
+            !inner.is_empty()
+                && inner
+                    .chars()
+                    .all(|c| matches!(c.to_ascii_lowercase(), 'h' | 'm' | 's'))
+        })
+}
+
+/// Reject strftime formats chrono cannot render, which would otherwise panic
+/// when the first date is written.
+pub(crate) fn validate_strftime(format: &str) -> Result<()> {
+    use chrono::format::{Item, StrftimeItems};
+
+    if StrftimeItems::new(format).any(|item| matches!(item, Item::Error)) {
+        return Err(Error::InvalidArgument(format!(
+            "invalid date format: {}",
+            format
+        )));
     }
+    Ok(())
+}
 
-    // Handle as text value
-    fmt.format_text(&raw_value, opts)
+/// Convert an Excel serial number to a date and time, rounded to the
+/// millisecond. In the 1900 system, serials before 60 account for Excel's
+/// fictitious 1900-02-29.
+pub(crate) fn serial_to_datetime(serial: f64, date1904: bool) -> Option<chrono::NaiveDateTime> {
+    if !serial.is_finite() || serial < 0.0 {
+        return None;
+    }
+
+    let (epoch, serial) = if date1904 {
+        (chrono::NaiveDate::from_ymd_opt(1904, 1, 1)?, serial)
+    } else if serial < 60.0 {
+        (chrono::NaiveDate::from_ymd_opt(1899, 12, 31)?, serial)
+    } else {
+        (chrono::NaiveDate::from_ymd_opt(1899, 12, 30)?, serial)
+    };
+
+    let millis = (serial * 86_400_000.0).round() as i64;
+    epoch
+        .and_hms_opt(0, 0, 0)?
+        .checked_add_signed(chrono::Duration::milliseconds(millis))
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn texts(rows: &[Vec<CellData>]) -> Vec<Vec<&str>> {
+        rows.iter()
+            .map(|r| r.iter().map(|c| c.text.as_str()).collect())
+            .collect()
+    }
+
+    #[test]
+    fn test_trim_and_drop_blank() {
+        let mut rows = grid(&[&["a", "", "b", ""], &["", "", "", ""], &["c", "", "", " "], &["", "", "", ""]]);
+        trim_trailing(&mut rows);
+        assert_eq!(texts(&rows), vec![vec!["a", "", "b"], vec!["", "", ""], vec!["c", "", ""]]);
+
+        drop_blank_cols(&mut rows);
+        assert_eq!(texts(&rows), vec![vec!["a", "b"], vec!["", ""], vec!["c", ""]]);
+    }
+
+    #[test]
+    fn test_combine_sheets() {
+        let jan = grid(&[&["Account", "Amount"], &["Rent", "100"]]);
+        let feb = grid(&[&["Amount", "Account", "Note"], &["25", "Power", "late"]]);
+        let combined = combine_sheets(vec![("Jan".to_string(), jan), ("Feb".to_string(), feb)]);
+        assert_eq!(
+            texts(&combined),
+            vec![
+                vec!["sheet", "Account", "Amount", "Note"],
+                vec!["Jan", "Rent", "100", ""],
+                vec!["Feb", "Power", "25", "late"],
+            ]
+        );
+    }
+
+    #[test]
+    fn test_transpose() {
+        let rows = grid(&[&["Name", "Ann", "Bob"], &["Age", "31"]]);
+        assert_eq!(
+            texts(&transpose(rows)),
+            vec![vec!["Name", "Age"], vec!["Ann", "31"], vec!["Bob", ""]]
+        );
+        assert!(transpose(Vec::new()).is_empty());
+    }
+
+    #[test]
+    fn test_schema_before_range_and_transpose() {
+        let config = CsvConfig {
+            schema: Some(toml::from_str("[columns]\nQty = { type = \"number\", format = \"0.00\" }").unwrap()),
+            range: Some(((2, 1), (2, 3))),
+            transpose: true,
+            ..Default::default()
+        };
+        let rows = grid(&[&["Item", "Qty"], &["Bolt", "5"], &["Nut", "7"]]);
+        let rows = transform_rows("Sheet1", rows, &SheetVisibility::default(), &config).unwrap();
+        assert_eq!(texts(&rows), vec![vec!["Qty", "5.00", "7.00"]]);
+    }
+
+    #[test]
+    fn test_unpivot() {
+        let spec: Unpivot = "id-cols=A".parse().unwrap();
+        assert_eq!(spec.id_cols, vec![0]);
+        assert_eq!("id-cols=C:A,E".parse::<Unpivot>().unwrap().id_cols, vec![0, 1, 2, 4]);
+        assert!("A:C".parse::<Unpivot>().is_err());
+
+        let rows = grid(&[&["Account", "Jan", "Feb"], &["Rent", "100", ""], &["Power", "20", "25"]]);
+        let melted = spec.apply(rows.clone());
+        assert_eq!(
+            texts(&melted),
+            vec![
+                vec!["Account", "variable", "value"],
+                vec!["Rent", "Jan", "100"],
+                vec!["Rent", "Feb", ""],
+                vec!["Power", "Jan", "20"],
+                vec!["Power", "Feb", "25"],
+            ]
+        );
+
+        let skip = Unpivot { skip_empty: true, ..spec };
+        assert_eq!(skip.apply(rows).len(), 4);
+    }
+
+    #[test]
+    fn test_detect_header_row() {
+        let rows = grid(&[
+            &["Quarterly Sales Report", "", ""],
+            &["", "", ""],
+            &["Prepared by finance", "", ""],
+            &["Region", "Units", "Revenue"],
+            &["East", "10", "1200.5"],
+            &["West", "7", "980"],
+            &["North", "3", "410"],
+            &["Total", "20", "2590.5"],
+            &["", "", ""],
+            &["Source: ERP export", "", ""],
+        ]);
+        assert_eq!(detect_header_row(&rows), Some(3));
+        assert_eq!(footer_start(&rows, 3), 7);
+
+        let numbers = grid(&[&["1", "2"], &["3", "4"], &["5", "6"]]);
+        assert_eq!(detect_header_row(&numbers), None);
+        assert_eq!(footer_start(&numbers, 0), 3);
+    }
+
+    #[test]
+    fn test_classify_date_format() {
+        assert_eq!(classify_date_format("General"), None);
+        assert_eq!(classify_date_format("#,##0.00"), None);
+        assert_eq!(classify_date_format("0.00E+00"), None);
+        assert_eq!(classify_date_format("\"Qty: \"0"), None);
+        assert_eq!(classify_date_format("m/d/yy"), Some(DateKind::Date));
+        assert_eq!(classify_date_format("dd-mmm-yyyy"), Some(DateKind::Date));
+        assert_eq!(classify_date_format("mmm"), Some(DateKind::Date));
+        assert_eq!(classify_date_format("yyyy-mm-dd hh:mm"), Some(DateKind::DateTime));
+        assert_eq!(classify_date_format("h:mm AM/PM"), Some(DateKind::Time));
+        assert_eq!(classify_date_format("mm:ss"), Some(DateKind::Time));
+        assert_eq!(classify_date_format("[h]:mm"), Some(DateKind::Time));
+        assert_eq!(classify_date_format("[$-407]dd.mm.yyyy"), Some(DateKind::Date));
+        assert!(is_elapsed_format("[h]:mm"));
+        assert!(is_elapsed_format("[mm]:ss.0"));
+        assert!(!is_elapsed_format("[$-407]dd.mm.yyyy"));
+        assert!(!is_elapsed_format("\"[h]\" 0"));
+    }
+
+    #[test]
+    fn test_date1904_shifts_only_calendar_dates() {
+        let config = CsvConfig {
+            date1904: true,
+            ..Default::default()
+        };
+        let opts = FormatOptions::default();
+        let format = |value, code| format_raw_value(&RawValue::Number(value), 164, code, &opts, &config);
+
+        assert_eq!(format(0.0, "yyyy-mm-dd"), "1904-01-01");
+        assert_eq!(format(1.5, "[h]:mm"), "36:00");
+        assert_eq!(format(0.75, "h:mm"), "18:00");
+    }
+
+    #[test]
+    fn test_precision() {
+        assert_eq!(format_full(0.1), "0.1");
+        assert_eq!(format_full(1234.5678), "1234.5678");
+        assert_eq!(format_full(0.1 + 0.2), "0.30000000000000004");
+        assert_eq!(format_full(1e300), "1e300");
+        assert_eq!(format_significant(2.0, 3), "2.00");
+        assert_eq!(format_significant(3.14159, 3), "3.14");
+        assert_eq!(format_significant(9.996, 3), "10.0");
+        assert_eq!(format_significant(12345.0, 3), "12300");
+        assert_eq!(format_significant(0.000123456, 2), "0.00012");
+        assert_eq!("full".parse::<Precision>(), Ok(Precision::Full));
+        assert_eq!("4".parse::<Precision>(), Ok(Precision::Significant(4)));
+        assert!("0".parse::<Precision>().is_err());
+    }
+
+    #[test]
+    fn test_newlines() {
+        let text = "line 1\r\nline 2\nline 3";
+        assert_eq!(Newlines::Keep.apply(text), text);
+        assert_eq!(Newlines::Space.apply(text), "line 1 line 2 line 3");
+        assert_eq!(Newlines::Escape.apply(text), "line 1\\nline 2\\nline 3");
+        assert_eq!(
+            "<br>".parse::<Newlines>().unwrap().apply(text),
+            "line 1<br>line 2<br>line 3"
+        );
+    }
+
+    #[test]
+    fn test_serial_to_datetime() {
+        let dt = |s, d1904| serial_to_datetime(s, d1904).unwrap().to_string();
+        assert_eq!(dt(1.0, false), "1900-01-01 00:00:00");
+        assert_eq!(dt(61.0, false), "1900-03-01 00:00:00");
+        assert_eq!(dt(45658.5, false), "2025-01-01 12:00:00");
+        assert_eq!(dt(0.0, true), "1904-01-01 00:00:00");
+        assert_eq!(dt(44196.0, true), "2025-01-01 00:00:00");
+    }
+
+    #[test]
+    fn test_write_grid_inline_comments() {
+        let mut rows = grid(&[&["Account", "", "Amount"], &["Rent", "x", "100"], &["Power", "y", "25"]]);
+        rows[1][0].comment = Some("check lease".to_string());
+        rows[2][1].comment = Some("estimate".to_string());
+
+        let mut out = Vec::new();
+        write_grid(&rows, &mut out, &CsvConfig::default()).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "Account,Account (comment),,B (comment),Amount\n\
+             Rent,check lease,x,,100\n\
+             Power,,y,estimate,25\n"
+        );
+    }
+}